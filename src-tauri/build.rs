@@ -1,6 +1,38 @@
 fn main() {
+    // asInvoker: main() relaunches elevated only for commands that write the hosts file,
+    // so read-only commands and --no-elevate never see a UAC prompt
     #[cfg(windows)]
-    println!("cargo:rustc-link-arg=/MANIFESTUAC:level=requireAdministrator uiAccess=false");
+    println!("cargo:rustc-link-arg=/MANIFESTUAC:level=asInvoker uiAccess=false");
+
+    emit_build_info();
 
     tauri_build::build()
 }
+
+/// Build details for `hosts::app_version`; anything unavailable is simply left unset.
+fn emit_build_info() {
+    let sha = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    if let Some(sha) = sha.filter(|s| !s.is_empty()) {
+        println!("cargo:rustc-env=HOSTLY_GIT_SHA={}", sha);
+    }
+
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH").ok().or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs().to_string())
+    });
+    if let Some(ts) = timestamp {
+        println!("cargo:rustc-env=HOSTLY_BUILD_TIMESTAMP={}", ts);
+    }
+
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
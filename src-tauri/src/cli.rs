@@ -1,44 +1,105 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use crate::storage;
 use tauri::AppHandle;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::io::Read;
 
 
 
 #[cfg(windows)]
 fn check_elevation() {
-    // Simple check: try to open the physical drive? No, too invasive.
-    // Try to open SC manager? 
-    // Let's use a simple reliable check: `net session`
-    let output = std::process::Command::new("net")
-        .arg("session")
-        .output();
-    match output {
-        Ok(out) => {
-             if out.status.success() {
-                 println!("[DIAGNOSTIC] Running as ADMIN");
-             } else {
-                 println!("[DIAGNOSTIC] Running as STANDARD USER");
-             }
-        },
-        Err(_) => println!("[DIAGNOSTIC] Failed to check elevation"),
+    if crate::hosts::is_elevated() {
+        eprintln!("[DIAGNOSTIC] Running as ADMIN");
+    } else {
+        eprintln!("[DIAGNOSTIC] Running as STANDARD USER");
     }
 }
 
 #[derive(Parser)]
 #[command(name = "hostly")]
-#[command(version = "1.0")]
-
+#[command(version = crate::hosts::VERSION)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Print machine-readable JSON output where supported
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Show what would change without writing the hosts file or Hostly's config
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Don't ask before destructive operations (required when stdin isn't a terminal)
+    #[arg(long, short, global = true)]
+    yes: bool,
+
+    /// Don't relaunch as administrator on startup (same as HOSTLY_NO_ELEVATE=1)
+    #[arg(long, global = true)]
+    no_elevate: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum ExportFormat {
+    /// Hostly full backup (JSON)
+    Backup,
+    /// Hostly full backup (YAML)
+    Yaml,
+    /// SwitchHosts v4 backup (JSON)
+    Switchhosts,
+    /// Merged hosts output (common config + active profiles); the name is ignored
+    Hosts,
+    /// Only the active entries, without Hostly's banner, section headers or comments
+    Clean,
+    /// Raw content of the named profile
+    Profile,
+    /// The named profile with its name, description and source URL (JSON)
+    Snippet,
+    /// The profiles listed with --profiles, with a SHA-256 checksum, for sharing with a team (JSON)
+    Bundle,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum ImportKind {
+    /// Hostly full backup (JSON, or YAML detected from the content)
+    Json,
+    /// Hostly full backup (YAML)
+    Yaml,
+    /// Content of the named profile
+    Profile,
+    /// Common config
+    Common,
+    /// A single profile exported with --format snippet (created or overwritten by its own name)
+    Snippet,
+    /// A team bundle exported with --format bundle (each profile created or overwritten by name)
+    Bundle,
+}
+
+/// How much of an apply's effect to print.
+#[derive(clap::Args)]
+struct DetailArgs {
+    /// Don't list the entries that were added or removed
+    #[arg(long, short, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// List every added and removed entry
+    #[arg(long, short)]
+    verbose: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// List all profiles
-    List,
+    List {
+        /// Include profile content in --json output
+        #[arg(long)]
+        content: bool,
+
+        /// Only list active profiles
+        #[arg(long)]
+        active: bool,
+    },
     /// Enable single selection mode
     Single,
     /// Enable multi selection mode
@@ -46,16 +107,36 @@ enum Commands {
     /// Open/Activate specific profiles
     Open {
         /// Profile names to activate
-        #[arg(required = true)]
+        #[arg(required_unless_present = "from_file")]
         names: Vec<String>,
 
+        /// Also read profile names from this file, one per line ("-" for stdin; blank lines and # comments are skipped)
+        #[arg(long)]
+        from_file: Option<String>,
+
         /// Force multi-select mode if multiple profiles are provided
         #[arg(long, short)]
         multi: bool,
+
+        #[command(flatten)]
+        detail: DetailArgs,
     },
     /// Close/Deactivate specific profiles
     Close {
         /// Profile names to deactivate
+        #[arg(required_unless_present = "from_file")]
+        names: Vec<String>,
+
+        /// Also read profile names from this file, one per line ("-" for stdin; blank lines and # comments are skipped)
+        #[arg(long)]
+        from_file: Option<String>,
+
+        #[command(flatten)]
+        detail: DetailArgs,
+    },
+    /// Flip profiles on/off regardless of their current state
+    Toggle {
+        /// Profile names to toggle
         #[arg(required = true)]
         names: Vec<String>,
     },
@@ -64,19 +145,41 @@ enum Commands {
         /// Profile name to export (Optional, exports full backup if missing)
         name: Option<String>,
         
-        /// Output file path
+        /// Output file path ("-" for stdout)
         #[arg(long, short, required = true)]
         target: String,
+
+        /// Output format (defaults to `profile` if a name is given, otherwise `yaml` for
+        /// *.yaml/*.yml targets and `backup` for anything else)
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+
+        /// Profiles to put in a bundle, comma separated (implies --format bundle)
+        #[arg(long, value_delimiter = ',')]
+        profiles: Vec<String>,
     },
     /// Import profile or common config
     Import {
         /// Profile name to import as. If missing, imports as Common Config.
         name: Option<String>,
         
-        /// Input file path
+        /// Input file path ("-" for stdin)
         #[arg(long, short, required = true)]
         target: String,
 
+        /// What the input is. Defaults to profile if a name is given, else json for *.json files
+        /// (snippet or bundle when the content is one), yaml for *.yaml/*.yml files, else common
+        #[arg(long = "as", alias = "format", value_enum)]
+        kind: Option<ImportKind>,
+
+        /// For bundles: refuse to import if the checksum doesn't match the contents
+        #[arg(long)]
+        verify: bool,
+
+        /// For JSON backups: keep existing profiles and merge the backup in instead of replacing everything
+        #[arg(long)]
+        merge: bool,
+
         /// Activate specific profiles after import. If no profiles listed, activates the imported profile (if named).
         #[arg(long, num_args(0..))]
         open: Option<Vec<String>>,
@@ -89,11 +192,610 @@ enum Commands {
         #[arg(long, short)]
         single: bool,
     },
+    /// Save the current system hosts file as a new profile
+    ImportCurrent {
+        /// Name for the new profile
+        #[arg(long)]
+        name: String,
+
+        /// Keep Hostly's generated block instead of stripping it
+        #[arg(long)]
+        keep_managed: bool,
+    },
+    /// Copy the current system hosts file verbatim into a new, locked profile
+    Snapshot {
+        /// Name for the new profile
+        #[arg(long, default_value = storage::SYSTEM_BACKUP_NAME)]
+        name: String,
+
+        /// Leave the new profile editable
+        #[arg(long)]
+        unlocked: bool,
+    },
     /// Migrate from SwitchHosts
     Migration {
         /// SwitchHosts backup file path (json)
         #[arg(long, short, required = true)]
         target: String,
+    },
+    /// Flush the OS DNS resolver cache
+    FlushDns,
+    /// Print the hosts file as `apply` would write it, without writing it
+    Preview,
+    /// Print the IP a hostname will map to once the current config is applied (exit code 1 if undefined)
+    Resolve {
+        hostname: String,
+    },
+    /// Show which profile a hostname's pending entry comes from (exit code 1 if undefined)
+    Whoami {
+        hostname: String,
+    },
+    /// Show a diff between the system hosts and the pending config (exit code 1 if changes are pending)
+    Diff,
+    /// Restore the system hosts to its state before the last apply
+    Undo,
+    /// Remove Hostly's entries from the system hosts and deactivate every profile (undo with `undo`)
+    DisableAll,
+    /// Activate the profiles whose network rule (Wi-Fi SSID or gateway MAC) matches the current network
+    Auto,
+    /// Re-apply the current config to the system hosts
+    Apply,
+    /// Show selection mode, active profiles and whether the hosts file is up to date
+    Status,
+    /// Show where Hostly stores its config, profiles and backups
+    Paths,
+    /// Print the version with the commit, build date and platform
+    Version,
+    /// Print a shell completion script (bash, zsh, fish, powershell, elvish)
+    Completions {
+        shell: clap_complete::Shell,
+    },
+    /// Create a new profile
+    Create {
+        /// Profile name
+        name: String,
+
+        /// Read initial content from this file ("-" for stdin)
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Delete a profile
+    Delete {
+        /// Profile name
+        name: String,
+
+        /// Delete even if the profile is currently active (asks for confirmation unless --yes)
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Rename a profile
+    Rename {
+        /// Current profile name
+        name: String,
+        /// New profile name
+        new_name: String,
+    },
+    /// Copy a profile under a new name (the copy starts inactive)
+    Duplicate {
+        /// Source profile name
+        name: String,
+        /// Name for the copy
+        new_name: String,
+    },
+    /// Check that the IPs active entries point at accept TCP connections (exit code 1 if any don't)
+    Check {
+        /// Port to connect to
+        #[arg(long, default_value_t = storage::DEFAULT_CHECK_PORT)]
+        port: u16,
+    },
+    /// Tidy a profile in place: normalized spacing, duplicate entries and extra blank lines removed
+    Fmt {
+        /// Profile name
+        name: String,
+
+        /// Also sort each block of entries by hostname
+        #[arg(long)]
+        sort: bool,
+    },
+    /// Check a profile for likely mistakes such as a redefined localhost (exit code 1 if any are found)
+    Lint {
+        /// Profile name
+        name: String,
+    },
+    /// Search profile names and content (case-insensitive)
+    Search {
+        /// Text to look for
+        query: String,
+    },
+    /// Compare the entries of two profiles (exit code 1 if they differ)
+    DiffProfiles {
+        /// First profile name
+        name_a: String,
+        /// Second profile name
+        name_b: String,
+    },
+    /// Download a hosts list from a URL into a profile
+    ImportUrl {
+        /// Source URL (http/https)
+        url: String,
+
+        /// Profile name to import as (created if missing)
+        #[arg(long, short)]
+        name: String,
+    },
+    /// Re-download remote profiles (by default only those whose update interval has elapsed)
+    Refresh {
+        /// Refresh every profile that has a source URL
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+
+        /// Refresh a single profile by name
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+    /// Write a timestamped full backup, keeping only the newest ones
+    Backup {
+        /// Directory to write into (default: the app data dir's backups folder)
+        dir: Option<String>,
+
+        /// How many backups to keep in the directory (0 keeps all)
+        #[arg(long, default_value_t = 20)]
+        keep: usize,
+    },
+    /// Restore a full backup (the newest one in the backups folder by default) and re-apply
+    Restore {
+        /// Backup file to restore from
+        file: Option<String>,
+    },
+    /// Delete profile files no longer referenced by the config
+    Gc,
+    /// Rebuild an unreadable config.json from the profile files (all profiles come back inactive)
+    Repair,
+    /// Move the data directory (config, profiles, backups) to DIR and use it from now on
+    Relocate {
+        dir: String,
+    },
+    /// Watch profile files and re-apply when an active profile or the common config changes
+    Watch,
+    /// List profile names one per line (used by completion scripts)
+    #[command(name = "__complete-names", hide = true)]
+    CompleteNames,
+}
+
+impl Commands {
+    /// Commands that never write the system hosts file and so don't need elevation.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Commands::List { .. }
+                | Commands::Export { .. }
+                | Commands::Preview
+                | Commands::Resolve { .. }
+                | Commands::Whoami { .. }
+                | Commands::Diff
+                | Commands::Status
+                | Commands::Paths
+                | Commands::Version
+                | Commands::Completions { .. }
+                | Commands::Search { .. }
+                | Commands::Check { .. }
+                | Commands::Lint { .. }
+                | Commands::DiffProfiles { .. }
+                | Commands::Backup { .. }
+                | Commands::Gc
+                | Commands::Repair
+                | Commands::CompleteNames
+        )
+    }
+}
+
+/// The positional names plus those listed in `from_file`, without duplicates, in order.
+fn collect_names(names: Vec<String>, from_file: Option<&str>) -> Result<Vec<String>, String> {
+    let listed = match from_file {
+        Some(path) => read_input(path)
+            .map_err(|e| format!("Failed to read names from '{}': {}", path, e))?
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut out: Vec<String> = Vec::new();
+    for name in names.into_iter().chain(listed) {
+        if !out.contains(&name) {
+            out.push(name);
+        }
+    }
+    Ok(out)
+}
+
+/// Subcommands whose positional arguments are profile names.
+const NAME_COMMANDS: [&str; 3] = ["open", "close", "toggle"];
+
+/// Hooks `__complete-names` into a clap-generated completion script so the
+/// `NAME_COMMANDS` complete profile names. PowerShell and elvish are left as generated.
+fn with_name_completion(shell: clap_complete::Shell, bin: &str, script: String) -> String {
+    use clap_complete::Shell;
+
+    // Names as clap_complete gives them to the functions it generates
+    let func = bin.replace('-', "__");
+    match shell {
+        Shell::Bash => format!(
+            r#"{script}
+_{func}_profile_names() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}" i
+    if [[ "$cur" != -* && "$prev" != --from-file ]]; then
+        for ((i = 1; i < COMP_CWORD; i++)); do
+            [[ "${{COMP_WORDS[i]}}" == -* ]] && continue
+            case "${{COMP_WORDS[i]}}" in
+                {cases})
+                    local IFS=$'\n'
+                    COMPREPLY=($(compgen -W "$({bin} __complete-names 2>/dev/null)" -- "$cur"))
+                    return 0
+                    ;;
+            esac
+            break
+        done
+    fi
+    _{func} "$@"
+}}
+complete -F _{func}_profile_names -o bashdefault -o default {bin}
+"#,
+            cases = NAME_COMMANDS.join("|"),
+        ),
+        // The generated script is also the autoloaded function, so the helper goes right
+        // below `#compdef` and the name arguments point at it instead of `_default`
+        Shell::Zsh => {
+            let helper = format!(
+                "_{func}_profile_names() {{\n    local -a names\n    names=(${{(f)\"$({bin} __complete-names 2>/dev/null)\"}})\n    compadd -a names\n}}\n",
+            );
+            let mut out = String::new();
+            for (idx, line) in script.lines().enumerate() {
+                if line.trim_start().starts_with("'*::names -- Profile names to ") {
+                    out.push_str(&line.replace(":_default'", &format!(":_{func}_profile_names'")));
+                } else {
+                    out.push_str(line);
+                }
+                out.push('\n');
+                if idx == 0 {
+                    out.push('\n');
+                    out.push_str(&helper);
+                }
+            }
+            out
+        }
+        Shell::Fish => format!(
+            "{script}complete -c {bin} -n \"__fish_seen_subcommand_from {}\" -f -a \"({bin} __complete-names 2>/dev/null)\"\n",
+            NAME_COMMANDS.join(" "),
+        ),
+        _ => script,
+    }
+}
+
+/// Reads a CLI input file, where "-" means stdin.
+fn read_input(source: &str) -> Result<String, String> {
+    if source == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf).map_err(|e| e.to_string())?;
+        Ok(buf)
+    } else {
+        fs::read_to_string(expand_path(source)).map_err(|e| e.to_string())
+    }
+}
+
+/// Expands a leading `~` plus `$VAR`, `${VAR}` and `%VAR%` references in a path argument,
+/// for quoted paths and shells that don't do it themselves. Unset variables are kept as written.
+fn expand_path(raw: &str) -> PathBuf {
+    let mut out = String::new();
+    let mut rest = raw;
+    if let Some(after) = raw.strip_prefix('~') {
+        if after.is_empty() || after.starts_with('/') || after.starts_with('\\') {
+            if let Ok(home) = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")) {
+                out.push_str(&home);
+                rest = after;
+            }
+        }
+    }
+
+    let chars: Vec<char> = rest.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let (name, next) = match chars[i] {
+            '$' => {
+                let braced = chars.get(i + 1) == Some(&'{');
+                let start = if braced { i + 2 } else { i + 1 };
+                let mut end = start;
+                while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                match braced {
+                    true if chars.get(end) == Some(&'}') => (chars[start..end].iter().collect(), end + 1),
+                    true => (String::new(), 0),
+                    false => (chars[start..end].iter().collect(), end),
+                }
+            },
+            '%' => match chars[i + 1..].iter().position(|&c| c == '%') {
+                Some(len) => (chars[i + 1..i + 1 + len].iter().collect(), i + len + 2),
+                None => (String::new(), 0),
+            },
+            _ => (String::new(), 0),
+        };
+
+        match std::env::var(&name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&value);
+                i = next;
+            },
+            _ => {
+                out.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(out)
+}
+
+/// What a command reports. Without `--json` text goes out as it's produced; with it
+/// everything is collected and printed as one object on stdout when the command is done.
+struct Output {
+    json: bool,
+    failed: bool,
+    messages: Vec<String>,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+    fields: serde_json::Map<String, serde_json::Value>,
+    result: Option<serde_json::Value>,
+}
+
+impl Output {
+    fn new(json: bool) -> Self {
+        Output {
+            json,
+            failed: false,
+            messages: Vec::new(),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            fields: serde_json::Map::new(),
+            result: None,
+        }
+    }
+
+    /// A line of the command's result (stdout).
+    fn say(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        if self.json {
+            self.messages.push(line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    /// Progress and side notes, kept off stdout so it carries only the result.
+    fn note(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        if self.json {
+            self.messages.push(line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn warn(&mut self, warning: impl Into<String>) {
+        let warning = warning.into();
+        if self.json {
+            self.warnings.push(warning);
+        } else {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+
+    /// Something that went wrong after the command's main work was done.
+    fn error(&mut self, error: impl Into<String>) {
+        let error = error.into();
+        if self.json {
+            self.errors.push(error);
+        } else {
+            eprintln!("Error: {}", error);
+        }
+    }
+
+    /// A named value of the JSON result; plain output doesn't show it.
+    fn set(&mut self, key: &str, value: impl serde::Serialize) {
+        if self.json {
+            self.fields.insert(key.to_string(), serde_json::to_value(value).unwrap_or_default());
+        }
+    }
+
+    /// The whole JSON result, for commands whose output has a shape of its own.
+    fn emit(&mut self, value: impl serde::Serialize) {
+        self.result = Some(serde_json::to_value(value).unwrap_or_default());
+    }
+
+    /// Reports a failed command. The code comes from the storage error; plain `String`
+    /// errors carry the generic `other` code.
+    fn fail(&mut self, context: impl std::fmt::Display, error: impl Into<storage::StorageError>) {
+        let error = error.into();
+        self.reject(error.code(), format!("{}: {}", context, error));
+    }
+
+    /// Reports a storage error with its own (localized) message.
+    fn fail_with(&mut self, error: storage::StorageError) {
+        self.reject(error.code(), &error);
+    }
+
+    /// Reports a problem with how the command was invoked, which no storage error describes.
+    fn reject(&mut self, code: &str, message: impl std::fmt::Display) {
+        self.failed = true;
+        report_error(self.json, code, message);
+    }
+
+    /// Prints the collected JSON result.
+    fn finish(self) {
+        if !self.json {
+            return;
+        }
+        let value = self.result.unwrap_or_else(|| {
+            let mut object = self.fields;
+            object.insert("ok".to_string(), (!self.failed).into());
+            object.insert("messages".to_string(), self.messages.into());
+            object.insert("warnings".to_string(), self.warnings.into());
+            object.insert("errors".to_string(), self.errors.into());
+            serde_json::Value::Object(object)
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default());
+    }
+}
+
+/// Prints an apply's summary line and whatever went wrong along the way.
+fn report_apply(out: &mut Output, summary: &storage::ApplySummary) {
+    out.say(format!("Hosts applied: {}.", summary));
+    print_apply_warnings(out, summary);
+}
+
+/// Prints the apply summary plus the entries it added/removed, capped unless verbose.
+fn print_apply_summary(out: &mut Output, summary: &storage::ApplySummary, detail: &DetailArgs) {
+    const CAP: usize = 10;
+
+    report_apply(out, summary);
+    // The JSON result carries the full entry lists
+    if detail.quiet || out.json {
+        return;
+    }
+    for (sign, entries) in [("+", &summary.added_entries), ("-", &summary.removed_entries)] {
+        let shown = if detail.verbose { entries.len() } else { entries.len().min(CAP) };
+        for entry in &entries[..shown] {
+            out.say(format!("  {} {}", sign, entry));
+        }
+        if shown < entries.len() {
+            out.say(format!("  {} ...and {} more", sign, entries.len() - shown));
+        }
+    }
+}
+
+fn print_apply_warnings(out: &mut Output, summary: &storage::ApplySummary) {
+    for warning in &summary.warnings {
+        out.warn(warning.as_str());
+    }
+    for error in &summary.errors {
+        out.error(error.as_str());
+    }
+    out.set("apply", summary);
+}
+
+/// Re-applies after a config change, printing any warnings from the apply.
+fn reapply(ctx: &storage::Context, out: &mut Output) -> Result<storage::ApplySummary, storage::StorageError> {
+    let summary = storage::apply_config_with_summary_internal(ctx)?;
+    print_apply_warnings(out, &summary);
+    Ok(summary)
+}
+
+/// Reports a failed command on stderr. With `--json` scripts get a stable
+/// `{ "error": true, "code": ..., "message": ... }` object instead of free text.
+fn report_error(json: bool, code: &str, message: impl std::fmt::Display) {
+    if json {
+        eprintln!("{}", serde_json::json!({ "error": true, "code": code, "message": message.to_string() }));
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+/// Asks a yes/no question on the terminal. Without a terminal there's nobody to ask,
+/// so only an explicit `--yes` counts as consent.
+fn confirm(question: &str, assume_yes: bool) -> bool {
+    use std::io::{IsTerminal, Write};
+
+    if assume_yes {
+        return true;
+    }
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Refusing to continue without --yes in non-interactive mode.");
+        return false;
+    }
+
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Copies the app dir (config, common config, profiles) into a temp dir for `--dry-run`.
+fn make_dry_run_dir(real: Option<&PathBuf>) -> Result<tempfile::TempDir, String> {
+    let scratch = tempfile::tempdir().map_err(|e| e.to_string())?;
+    if let Some(real) = real.filter(|p| p.exists()) {
+        copy_dir(real, scratch.path()).map_err(|e| e.to_string())?;
+    }
+    Ok(scratch)
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Relative paths of all files under `root`, skipping Hostly's own .bak files.
+fn list_files(root: &Path, dir: &Path, out: &mut std::collections::BTreeSet<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            list_files(root, &path, out);
+        } else if path.extension().map(|e| e != "bak").unwrap_or(true) {
+            if let Ok(rel) = path.strip_prefix(root) {
+                out.insert(rel.to_path_buf());
+            }
+        }
+    }
+}
+
+/// Prints (to stderr, like the hosts preview) a diff for every app-dir file the dry run
+/// would have created, changed or removed.
+fn report_dry_run_changes(real: &Path, scratch: &Path) {
+    let mut files = std::collections::BTreeSet::new();
+    list_files(real, real, &mut files);
+    list_files(scratch, scratch, &mut files);
+
+    let mut changed = false;
+    for rel in files {
+        let before = fs::read_to_string(real.join(&rel)).unwrap_or_default();
+        let after = fs::read_to_string(scratch.join(&rel)).unwrap_or_default();
+        let label = rel.to_string_lossy();
+        let diff = crate::hosts::unified_diff(&before, &after, &label, &format!("{} (dry run)", label));
+        if !diff.is_empty() {
+            changed = true;
+            eprintln!("[dry-run] Would change {}:", label);
+            eprint!("{}", diff);
+        }
+    }
+    if !changed {
+        eprintln!("[dry-run] Hostly config would not change.");
+    }
+}
+
+/// Whether the current command line needs admin rights. The GUI and anything that may
+/// write the hosts file do; read-only queries, dry runs and help or usage errors don't.
+pub fn needs_elevation() -> bool {
+    if std::env::args().len() <= 1 {
+        return true;
+    }
+    match Cli::try_parse() {
+        Ok(cli) => match cli.command {
+            Some(command) => !cli.dry_run && !command.is_read_only(),
+            None => true, // No subcommand, runs the GUI
+        },
+        Err(_) => false,
     }
 }
 
@@ -123,8 +825,12 @@ pub fn run_cli(app: Option<&AppHandle>) -> bool {
             // If unknown command, print error and exit.
             // But we must distinguish if it's meant for Tauri?
             // Tauri doesn't really take args unless configured.
-            e.print().unwrap();
-            return true; // Exit app
+            // Usage errors exit 2 like clap's own; help and version exit 0
+            if e.use_stderr() && std::env::args().any(|a| a == "--json") {
+                report_error(true, "invalid_args", e.to_string().trim_end());
+                std::process::exit(2);
+            }
+            e.exit();
         }
     };
 
@@ -133,267 +839,1270 @@ pub fn run_cli(app: Option<&AppHandle>) -> bool {
         None => storage::Context::Headless,
     };
 
+    match run_command(cli, ctx) {
+        None => false,
+        Some(0) => true, // Command executed, exit app
+        Some(code) => std::process::exit(code),
+    }
+}
+
+/// Runs a parsed command against `ctx` and returns its exit code, or None if there
+/// was no subcommand and the GUI should start instead.
+fn run_command(cli: Cli, ctx: storage::Context) -> Option<i32> {
+    // Dry runs operate on a throwaway copy of the app dir, so every command
+    // behaves exactly as it would for real and we diff the copy afterwards
+    let real_app_dir = ctx.get_app_dir();
+    let scratch = if cli.dry_run {
+        match make_dry_run_dir(real_app_dir.as_ref().ok()) {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                report_error(cli.json, "dry_run_failed", format!("Failed to prepare dry run: {}", e));
+                return Some(1);
+            }
+        }
+    } else {
+        None
+    };
+    let ctx = match &scratch {
+        Some(dir) => storage::Context::DryRun(dir.path().to_path_buf()),
+        None => ctx,
+    };
+
+    let mut exit_code = 0;
+    let mut out = Output::new(cli.json);
+    // A dry run changes nothing, so there's nothing to confirm
+    let assume_yes = cli.yes || ctx.is_dry_run();
+
     match cli.command {
-        Some(Commands::List) => {
-            match storage::list_profiles_internal(&ctx) {
+        Some(Commands::List { content, active }) => {
+            let profiles = if active {
+                storage::list_active_profiles_internal(&ctx)
+            } else {
+                storage::list_profiles_internal(&ctx)
+            };
+            match profiles {
+                Ok(profiles) if cli.json => {
+                    let items: Vec<serde_json::Value> = profiles.iter().map(|p| {
+                        let mut item = serde_json::to_value(p).unwrap_or_default();
+                        if !content {
+                            if let Some(obj) = item.as_object_mut() {
+                                obj.remove("content");
+                            }
+                        }
+                        item
+                    }).collect();
+                    out.emit(&items);
+                }
                 Ok(profiles) => {
                     for p in profiles {
-                        println!("{} [{}]", p.name, if p.active { "ACTIVE" } else { "OFF" });
+                        out.say(format!("{} [{}]", p.name, if p.active { "ACTIVE" } else { "OFF" }));
                     }
                 }
-                Err(e) => eprintln!("Error listing profiles: {}", e),
+                Err(e) => {
+                    out.fail("Error listing profiles", e);
+                    exit_code = 1;
+                }
             }
         },
         Some(Commands::Single) => {
             if let Err(e) = storage::set_multi_select_internal(&ctx, false) {
-                eprintln!("Error setting single mode: {}", e);
+                out.fail("Error setting single mode", e);
+                exit_code = 1;
             } else {
-                 println!("Single selection mode enabled.");
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
+                 out.say("Single selection mode enabled.");
+                 if let Err(e) = reapply(&ctx, &mut out) {
+                     out.fail("Failed to apply hosts", e);
+                     exit_code = 1;
                  }
 
             }
         },
         Some(Commands::Multi) => {
              if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                eprintln!("Error setting multi mode: {}", e);
+                out.fail("Error setting multi mode", e);
+                exit_code = 1;
             } else {
-                 println!("Multi selection mode enabled.");
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
+                 out.say("Multi selection mode enabled.");
+                 if let Err(e) = reapply(&ctx, &mut out) {
+                     out.fail("Failed to apply hosts", e);
+                     exit_code = 1;
                  }
 
             }
         },
-        Some(Commands::Open { names, multi }) => {
-            if multi {
+        Some(Commands::Open { names, from_file, multi, detail }) => {
+            let names = collect_names(names, from_file.as_deref()).unwrap_or_else(|e| {
+                out.fail_with(storage::StorageError::IoError(e));
+                exit_code = 1;
+                Vec::new()
+            });
+            let mut missing: Vec<String> = Vec::new();
+
+            if exit_code == 0 && multi {
                 if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                    eprintln!("Error enabling multi-mode: {}", e);
-                    return true;
+                    out.fail("Error enabling multi-mode", e);
+                    exit_code = 1;
                 }
             }
 
-            // Check mode
-            let config = storage::load_config_internal(&ctx).unwrap_or_default();
-            if !config.multi_select && names.len() > 1 {
-                eprintln!("Warning: Single select mode is active. Only the first profile '{}' will be activated.", names[0]);
-                eprintln!("Use --multi to enable multi-select mode automatically.");
-            }
+            if exit_code == 0 {
+                // Check mode
+                let config = storage::load_config_internal(&ctx).unwrap_or_default();
+                if !config.multi_select && names.len() > 1 {
+                    out.warn(format!("Single select mode is active. Only the first profile '{}' will be activated.", names[0]));
+                    out.note("Use --multi to enable multi-select mode automatically.");
+                }
 
-            for name in names {
-                if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &name) {
-                    let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
-                    let p = current_profiles.iter().find(|p| p.id == id);
-                    if let Some(prof) = p {
-                        if !prof.active {
-                             if let Err(e) = storage::toggle_profile_active_internal(&ctx, &id) {
-                                  eprintln!("Failed to open '{}': {}", name, e);
-                             } else {
-                                  println!("Opened '{}'", name);
-                             }
-                        } else {
-                             println!("'{}' is already active.", name);
+                for name in names {
+                    if let Ok(Some(id)) = storage::resolve_profile(&ctx, &name) {
+                        let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
+                        let p = current_profiles.iter().find(|p| p.id == id);
+                        if let Some(prof) = p {
+                            if !prof.active {
+                                 if let Err(e) = storage::toggle_profile_active_internal(&ctx, &id) {
+                                      out.fail(format!("Failed to open '{}'", name), e);
+                                      exit_code = 1;
+                                 } else {
+                                      out.say(format!("Opened '{}'", name));
+                                 }
+                            } else {
+                                 out.say(format!("'{}' is already active.", name));
+                            }
                         }
+                    } else {
+                         missing.push(name);
+                    }
+                }
+                match storage::apply_config_with_summary_internal(&ctx) {
+                    Ok(summary) => print_apply_summary(&mut out, &summary, &detail),
+                    Err(e) => {
+                        out.fail("Failed to apply hosts", e);
+                        exit_code = 1;
                     }
-                } else {
-                     eprintln!("Profile '{}' not found.", name);
                 }
             }
-            if let Err(e) = storage::apply_config_internal(&ctx) {
-                eprintln!("Failed to apply hosts: {}", e);
+            if !missing.is_empty() {
+                out.fail_with(storage::StorageError::ProfileNotFound { id: missing.join(", ") });
+                exit_code = 1;
             }
 
         },
-        Some(Commands::Close { names }) => {
+        Some(Commands::Close { names, from_file, detail }) => {
+             let names = collect_names(names, from_file.as_deref()).unwrap_or_else(|e| {
+                 out.fail_with(storage::StorageError::IoError(e));
+                 exit_code = 1;
+                 Vec::new()
+             });
+             let mut missing: Vec<String> = Vec::new();
+
+             // Nothing to apply when the name list couldn't be read
+             let attempted = !names.is_empty();
              for name in names {
-                 if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &name) {
+                 if let Ok(Some(id)) = storage::resolve_profile(&ctx, &name) {
                       let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
                       if let Some(prof) = current_profiles.iter().find(|p| p.id == id) {
                            if prof.active {
                                 if let Err(e) = storage::toggle_profile_active_internal(&ctx, &id) {
-                                    eprintln!("Failed to close '{}': {}", name, e);
+                                    out.fail(format!("Failed to close '{}'", name), e);
+                                    exit_code = 1;
                                 } else {
-                                    println!("Closed '{}'", name);
+                                    out.say(format!("Closed '{}'", name));
                                 }
                            } else {
-                                println!("'{}' is already closed.", name);
+                                out.say(format!("'{}' is already closed.", name));
                            }
                       }
                  } else {
-                      eprintln!("Profile '{}' not found.", name);
+                      missing.push(name);
                  }
              }
-             if let Err(e) = storage::apply_config_internal(&ctx) {
-                 eprintln!("Failed to apply hosts: {}", e);
+             if attempted {
+                 match storage::apply_config_with_summary_internal(&ctx) {
+                     Ok(summary) => print_apply_summary(&mut out, &summary, &detail),
+                     Err(e) => {
+                         out.fail("Failed to apply hosts", e);
+                         exit_code = 1;
+                     }
+                 }
+             }
+             if !missing.is_empty() {
+                 out.fail_with(storage::StorageError::ProfileNotFound { id: missing.join(", ") });
+                 exit_code = 1;
              }
 
         },
-        Some(Commands::Export { name, target }) => {
-            if let Some(n) = name {
-                // Export Single
-                if let Ok(Some(id)) = storage::find_profile_id_by_name_internal(&ctx, &n) {
-                     let current_profiles = storage::list_profiles_internal(&ctx).unwrap_or_default();
-                     if let Some(p) = current_profiles.iter().find(|p| p.id == id) {
-                          if let Err(e) = fs::write(&target, &p.content) {
-                               eprintln!("Failed to write file: {}", e);
-                          } else {
-                               println!("Exported '{}' to '{}'", n, target);
-                          }
+        Some(Commands::Toggle { names }) => {
+             for name in names {
+                 match storage::resolve_profile(&ctx, &name) {
+                     Ok(Some(id)) => match storage::toggle_profile_active_internal(&ctx, &id) {
+                         Ok(()) => {
+                             let active = storage::load_config_internal(&ctx)
+                                 .map(|c| c.profiles.iter().any(|p| p.id == id && p.active))
+                                 .unwrap_or(false);
+                             out.say(format!("'{}' is now {}", name, if active { "on" } else { "off" }));
+                         },
+                         Err(e) => {
+                             out.fail(format!("Failed to toggle '{}'", name), e);
+                             exit_code = 1;
+                         }
+                     },
+                     Ok(None) => {
+                         out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                         exit_code = 1;
+                     },
+                     Err(e) => {
+                         out.fail(format!("Failed to look up '{}'", name), e);
+                         exit_code = 1;
                      }
-                } else {
-                     eprintln!("Profile '{}' not found.", n);
-                }
+                 }
+             }
+             match storage::apply_config_with_summary_internal(&ctx) {
+                 Ok(summary) => report_apply(&mut out, &summary),
+                 Err(e) => {
+                     out.fail("Failed to apply hosts", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Export { name, target, format, profiles }) => {
+            let format = format.unwrap_or(if !profiles.is_empty() {
+                ExportFormat::Bundle
+            } else if name.is_some() {
+                ExportFormat::Profile
+            } else if storage::BackupFormat::for_path(&target) == storage::BackupFormat::Yaml {
+                ExportFormat::Yaml
             } else {
-                // Export All
-                match storage::export_data_internal(&ctx) {
-                     Ok(json) => {
-                          if let Err(e) = fs::write(&target, json) {
-                               eprintln!("Failed to write export file: {}", e);
-                          } else {
-                               println!("Full backup exported to '{}'", target);
-                          }
-                     },
-                     Err(e) => eprintln!("Export failed: {}", e),
+                ExportFormat::Backup
+            });
+
+            // (payload, description for the confirmation message)
+            let export: Result<(String, String), String> = match format {
+                ExportFormat::Backup => storage::export_data_internal(&ctx, storage::BackupFormat::Json)
+                    .map(|json| (json, "Full backup".to_string())),
+                ExportFormat::Yaml => storage::export_data_internal(&ctx, storage::BackupFormat::Yaml)
+                    .map(|yaml| (yaml, "Full backup".to_string())),
+                ExportFormat::Switchhosts => storage::export_switchhosts_internal(&ctx)
+                    .map(|json| (json, "SwitchHosts backup".to_string())),
+                ExportFormat::Hosts => storage::build_merged_hosts(&ctx)
+                    .map(|hosts| (hosts, "Merged hosts".to_string())),
+                ExportFormat::Clean => storage::build_clean_hosts(&ctx)
+                    .map(|hosts| (hosts, "Active entries".to_string())),
+                ExportFormat::Profile => match &name {
+                    Some(n) => match storage::resolve_profile(&ctx, n) {
+                        Ok(Some(id)) => storage::read_profile_content_internal(&ctx, &id)
+                            .map(|content| (content, format!("Profile '{}'", n))),
+                        _ => Err(format!("Profile '{}' not found.", n)),
+                    },
+                    None => Err("A profile name is required for --format profile.".to_string()),
+                },
+                ExportFormat::Snippet => match &name {
+                    Some(n) => match storage::resolve_profile(&ctx, n) {
+                        Ok(Some(id)) => storage::export_profile_snippet_internal(&ctx, &id)
+                            .map(|json| (json, format!("Profile '{}'", n)))
+                            .map_err(|e| e.to_string()),
+                        _ => Err(format!("Profile '{}' not found.", n)),
+                    },
+                    None => Err("A profile name is required for --format snippet.".to_string()),
+                },
+                ExportFormat::Bundle => {
+                    // The positional name counts as one more profile
+                    let names: Vec<&String> = name.iter().chain(profiles.iter()).collect();
+                    let mut ids = Vec::new();
+                    let mut missing = Vec::new();
+                    let mut lookup_error = None;
+                    for n in &names {
+                        match storage::resolve_profile(&ctx, n) {
+                            Ok(Some(id)) => ids.push(id),
+                            Ok(None) => missing.push(n.as_str()),
+                            Err(e) => lookup_error = Some(e),
+                        }
+                    }
+                    if names.is_empty() {
+                        Err("List the profiles to bundle with --profiles.".to_string())
+                    } else if let Some(e) = lookup_error {
+                        Err(e)
+                    } else if !missing.is_empty() {
+                        Err(format!("Profiles not found: {}", missing.join(", ")))
+                    } else {
+                        storage::export_bundle_internal(&ctx, &ids)
+                            .map(|json| (json, format!("Bundle of {} profile(s)", ids.len())))
+                            .map_err(|e| e.to_string())
+                    }
+                },
+            };
+
+            match export {
+                Ok((payload, what)) if ctx.is_dry_run() => {
+                    out.note(format!("[dry-run] Would write {} ({} bytes) to '{}'", what, payload.len(), target));
+                },
+                // stdout carries only the payload so it can be piped; --json makes it a field instead
+                Ok((payload, what)) if target == "-" => {
+                    use std::io::Write;
+                    let written = if cli.json {
+                        out.set("content", &payload);
+                        Ok(())
+                    } else {
+                        let mut stdout = std::io::stdout().lock();
+                        stdout.write_all(payload.as_bytes()).and_then(|_| stdout.flush())
+                    };
+                    if let Err(e) = written {
+                        out.fail("Failed to write to stdout", e);
+                        exit_code = 1;
+                    } else {
+                        out.note(format!("{} exported to stdout", what));
+                    }
+                },
+                Ok((payload, what)) => {
+                    if let Err(e) = fs::write(expand_path(&target), payload) {
+                        out.fail("Failed to write export file", e);
+                        exit_code = 1;
+                    } else {
+                        out.say(format!("{} exported to '{}'", what, target));
+                    }
+                },
+                Err(e) => {
+                    out.fail("Export failed", e);
+                    exit_code = 1;
                 }
             }
         },
-     Some(Commands::Import { name, target, open, multi, single }) => {
+     Some(Commands::Import { name, target, kind, verify, merge, open, multi, single }) => {
              // Check if target is a remote URL
              let is_remote = target.to_lowercase().starts_with("http://") || target.to_lowercase().starts_with("https://");
 
              if is_remote {
                  if let Some(n) = &name {
-                     println!("Importing remote profile '{}' from '{}'...", n, target);
+                     out.say(format!("Importing remote profile '{}' from '{}'...", n, target));
                      match storage::create_profile_internal(
                          &ctx,
                          n.clone(),
                          None,
                          Some(target.clone()),
-                         Some(3600) // Default 1 hour interval
+                         Some(3600), // Default 1 hour interval
+                         None
                      ) {
                          Ok(id) => {
-                             println!("Profile created (ID: {}). Downloading content...", id);
+                             out.say(format!("Profile created (ID: {}). Downloading content...", id));
+                             out.set("id", &id);
                              if let Err(e) = storage::trigger_profile_update_internal(&ctx, &id) {
-                                 eprintln!("Warning: Failed to download content: {}", e);
+                                 out.warn(format!("Failed to download content: {}", e));
                              } else {
-                                 println!("Content downloaded.");
+                                 out.say("Content downloaded.");
                              }
                          },
                          Err(e) => {
-                             eprintln!("Failed to create remote profile: {}", e);
-                             return true;
+                             out.fail("Failed to create remote profile", e);
+                             exit_code = 1;
                          }
                      }
                  } else {
-                     eprintln!("Error: --name is required when importing a remote URL.");
-                     return true;
+                     out.reject("invalid_args", "Error: --name is required when importing a remote URL.");
+                     exit_code = 1;
                  }
              } else {
-                 // Existing Header (File Import)
-                 let path = PathBuf::from(&target);
-                 if !path.exists() {
-                     eprintln!("Target file '{}' not found.", target);
-                     return true;
-                 }
+                 // Local file or stdin
+                 let import_mode = if merge { storage::ImportMode::Merge } else { storage::ImportMode::Replace };
+                 let source = if target == "-" { "stdin".to_string() } else { format!("'{}'", target) };
+
+                 match read_input(&target) {
+                     Ok(content) => {
+                         let kind = kind.unwrap_or(if name.is_some() {
+                             ImportKind::Profile
+                         } else if target.to_lowercase().ends_with(".json") {
+                             // A single-profile snippet carries its content at the top level,
+                             // a bundle its profiles next to a checksum
+                             let value = serde_json::from_str::<serde_json::Value>(&content).unwrap_or_default();
+                             if value.get("content").is_some_and(|c| c.is_string()) {
+                                 ImportKind::Snippet
+                             } else if value.get("sha256").is_some() && value.get("profiles").is_some_and(|p| p.is_array()) {
+                                 ImportKind::Bundle
+                             } else {
+                                 ImportKind::Json
+                             }
+                         } else if storage::BackupFormat::for_path(&target) == storage::BackupFormat::Yaml {
+                             ImportKind::Yaml
+                         } else {
+                             ImportKind::Common
+                         });
 
-                 let content = match fs::read_to_string(&path) {
-                     Ok(c) => c,
+                         match kind {
+                             ImportKind::Profile => match &name {
+                                 Some(n) => match storage::upsert_profile_internal(&ctx, n.clone(), content) {
+                                     Ok(_) => out.say(format!("Imported profile '{}'.", n)),
+                                     Err(e) => {
+                                         out.fail("Import failed", e);
+                                         exit_code = 1;
+                                     }
+                                 },
+                                 None => {
+                                     out.reject("invalid_args", "Error: a profile name is required with --as profile.");
+                                     exit_code = 1;
+                                 }
+                             },
+                             ImportKind::Json | ImportKind::Yaml => {
+                                 // JSON detects YAML content too; --as yaml insists on it
+                                 let format = (kind == ImportKind::Yaml).then_some(storage::BackupFormat::Yaml);
+                                 match storage::import_data_internal(&ctx, content, import_mode, format) {
+                                     Ok(skipped) => {
+                                         out.say(format!("Global backup imported from {}.", source));
+                                         skipped.into_iter().for_each(|s| out.warn(s));
+                                     },
+                                     Err(e) => {
+                                         out.fail("Failed to import global backup", e);
+                                         exit_code = 1;
+                                     }
+                                 }
+                             },
+                             ImportKind::Common => match storage::save_common_config_internal(&ctx, content) {
+                                 Ok(_) => out.say(format!("Common config updated from {}.", source)),
+                                 Err(e) => {
+                                     out.fail("Failed to save common config", e);
+                                     exit_code = 1;
+                                 }
+                             },
+                             ImportKind::Snippet => match storage::import_profile_snippet_internal(&ctx, &content) {
+                                 Ok(_) => out.say(format!("Profile snippet imported from {}.", source)),
+                                 Err(e) => {
+                                     out.fail("Failed to import profile snippet", e);
+                                     exit_code = 1;
+                                 }
+                             },
+                             ImportKind::Bundle => match storage::import_bundle_internal(&ctx, &content, verify) {
+                                 Ok(result) => {
+                                     if !result.checksum_ok {
+                                         out.warn("the bundle's checksum doesn't match its contents (use --verify to refuse such bundles).");
+                                     }
+                                     out.say(format!("Imported {} profile(s) from {}: {}", result.imported.len(), source, result.imported.join(", ")));
+                                     out.set("imported", &result.imported);
+                                 },
+                                 Err(e) => {
+                                     out.fail("Failed to import bundle", e);
+                                     exit_code = 1;
+                                 }
+                             },
+                         }
+                     },
                      Err(e) => {
-                          eprintln!("Failed to read file: {}", e);
-                          return true;
+                          out.fail(format!("Failed to read {}", source), storage::StorageError::IoError(e));
+                          exit_code = 1;
                      }
-                 };
-
-                 if let Some(n) = &name {
-                      match storage::upsert_profile_internal(&ctx, n.clone(), content) {
-                           Ok(_) => println!("Imported profile '{}'.", n),
-                           Err(e) => eprintln!("Import failed: {}", e)
-                      }
-                 } else {
-                      if target.to_lowercase().ends_with(".json") {
-                          match storage::import_data_internal(&ctx, content) {
-                              Ok(_) => println!("Global backup imported from '{}'.", target),
-                              Err(e) => eprintln!("Failed to import global backup: {}", e),
-                          }
-                      } else {
-                           match storage::save_common_config_internal(&ctx, content) {
-                                Ok(_) => println!("Common config updated from '{}'.", target),
-                                Err(e) => eprintln!("Failed to save common config: {}", e)
-                           }
-                      }
                  }
              }
-             
+
              // Common Post-Processing (Open/Multi/Single)
-             let mut profiles_to_open = Vec::new();
-             if let Some(args) = open {
-                 if args.is_empty() {
-                     if let Some(n) = &name {
-                         profiles_to_open.push(n.clone());
+             if exit_code == 0 {
+                 let mut profiles_to_open = Vec::new();
+                 if let Some(args) = open {
+                     if args.is_empty() {
+                         if let Some(n) = &name {
+                             profiles_to_open.push(n.clone());
+                         }
+                     } else {
+                         profiles_to_open = args;
                      }
-                 } else {
-                     profiles_to_open = args;
                  }
-             }
 
-             if profiles_to_open.len() > 1 || multi {
-                  if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
-                      eprintln!("Error enabling multi-select mode: {}", e);
-                  }
-             } else if single {
-                  if let Err(e) = storage::set_multi_select_internal(&ctx, false) {
-                      eprintln!("Error enabling single-select mode: {}", e);
-                  }
-             }
-
-             for p_name in profiles_to_open {
-                 if let Ok(Some(pid)) = storage::find_profile_id_by_name_internal(&ctx, &p_name) {
-                      let list = storage::list_profiles_internal(&ctx).unwrap_or_default();
-                      if let Some(p) = list.iter().find(|p| p.id == pid) {
-                           if !p.active {
-                                let _ = storage::toggle_profile_active_internal(&ctx, &pid);
-                                println!("Profile '{}' activated.", p_name);
-                           } else {
-                                println!("Profile '{}' is already active.", p_name);
-                           }
+                 if profiles_to_open.len() > 1 || multi {
+                      if let Err(e) = storage::set_multi_select_internal(&ctx, true) {
+                          out.fail("Error enabling multi-select mode", e);
+                          exit_code = 1;
+                      }
+                 } else if single {
+                      if let Err(e) = storage::set_multi_select_internal(&ctx, false) {
+                          out.fail("Error enabling single-select mode", e);
+                          exit_code = 1;
                       }
-                 } else {
-                      eprintln!("Warning: Cannot open profile '{}' (not found).", p_name);
                  }
-             }
-             if let Err(e) = storage::apply_config_internal(&ctx) {
-                 eprintln!("Failed to apply hosts: {}", e);
+
+                 for p_name in profiles_to_open {
+                     if let Ok(Some(pid)) = storage::resolve_profile(&ctx, &p_name) {
+                          let list = storage::list_profiles_internal(&ctx).unwrap_or_default();
+                          if let Some(p) = list.iter().find(|p| p.id == pid) {
+                               if !p.active {
+                                    let _ = storage::toggle_profile_active_internal(&ctx, &pid);
+                                    out.say(format!("Profile '{}' activated.", p_name));
+                               } else {
+                                    out.say(format!("Profile '{}' is already active.", p_name));
+                               }
+                          }
+                     } else {
+                          out.warn(format!("Cannot open profile '{}' (not found).", p_name));
+                     }
+                 }
+                 if let Err(e) = reapply(&ctx, &mut out) {
+                     out.fail("Failed to apply hosts", e);
+                     exit_code = 1;
+                 }
              }
 
         },
         Some(Commands::Migration { target }) => {
-             let path = PathBuf::from(&target);
+             let path = expand_path(&target);
              if !path.exists() {
-                 eprintln!("Target file '{}' not found.", target);
-                 return true;
+                 out.fail_with(storage::StorageError::IoError(format!("Target file '{}' not found.", target)));
+                 exit_code = 1;
+             } else {
+                 match fs::read_to_string(&path) {
+                     Ok(content) => {
+                         match storage::import_switchhosts_internal(&ctx, content) {
+                             Ok(count) => {
+                                 out.say(format!("Successfully migrated {} profiles from SwitchHosts backup '{}'", count, target));
+                                 out.set("migrated", count);
+                                 if let Err(e) = reapply(&ctx, &mut out) {
+                                     out.fail("Failed to apply hosts", e);
+                                     exit_code = 1;
+                                 }
+                             },
+                             Err(e) => {
+                                 out.fail("Migration failed (is this a SwitchHosts JSON backup?)", e);
+                                 exit_code = 1;
+                             }
+                         }
+                     },
+                     Err(e) => {
+                          out.fail("Failed to read file", e);
+                          exit_code = 1;
+                     }
+                 }
+             }
+        },
+        Some(Commands::FlushDns) => {
+             match crate::hosts::flush_dns() {
+                 Ok(_) => out.say("DNS cache flushed."),
+                 Err(e) => {
+                     out.fail("Failed to flush DNS cache", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Preview) => {
+             match storage::build_pending_hosts(&ctx) {
+                 Ok(content) if cli.json => out.set("content", content),
+                 Ok(content) => print!("{}", content),
+                 Err(e) => {
+                     out.fail("Failed to build hosts", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Resolve { hostname }) => {
+             match storage::resolve_in_pending_internal(&ctx, &hostname) {
+                 Ok(ip) if cli.json => {
+                     out.emit(serde_json::json!({ "hostname": hostname, "ip": ip }));
+                     if ip.is_none() {
+                         exit_code = 1;
+                     }
+                 },
+                 Ok(Some(ip)) => out.say(ip),
+                 Ok(None) => {
+                     out.reject(
+                         storage::StorageError::HostnameNotFound { hostname: hostname.clone() }.code(),
+                         format!("'{}' is not defined in the hosts file or by any active profile.", hostname),
+                     );
+                     exit_code = 1;
+                 },
+                 Err(e) => {
+                     out.fail("Failed to build hosts", e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::Whoami { hostname }) => {
+             let wanted = hostname.trim().to_lowercase();
+             match storage::entry_provenance_internal(&ctx).map(|owners| owners.into_iter().find(|o| o.hostname == wanted)) {
+                 Ok(Some(owner)) if cli.json => out.emit(&owner),
+                 Ok(Some(owner)) => out.say(format!("{} -> {} ({})", owner.hostname, owner.ip, owner.profile)),
+                 Ok(None) => {
+                     out.reject(
+                         storage::StorageError::HostnameNotFound { hostname: hostname.clone() }.code(),
+                         format!("'{}' is not defined by the common config or any active profile.", hostname),
+                     );
+                     exit_code = 1;
+                 },
+                 Err(e) => {
+                     out.fail("Failed to build hosts", e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::Diff) => {
+             match crate::hosts::diff_pending(&ctx) {
+                 Ok(diff) if diff.is_empty() => out.say("No pending changes."),
+                 Ok(diff) if cli.json => {
+                     out.set("diff", diff);
+                     exit_code = 1;
+                 },
+                 Ok(diff) => {
+                     print!("{}", diff);
+                     exit_code = 1;
+                 },
+                 Err(e) => {
+                     out.fail("Failed to compute diff", e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::Undo) => {
+             match crate::hosts::undo_last_apply(&ctx) {
+                 Ok(problems) => {
+                     out.say("Restored the hosts file to its state before the last apply.");
+                     problems.into_iter().for_each(|p| out.error(p));
+                 },
+                 Err(e) => {
+                     out.fail("Undo failed", e);
+                     exit_code = 1;
+                 }
              }
+        },
+        Some(Commands::DisableAll) => {
+             match crate::hosts::disable_all(&ctx) {
+                 Ok(problems) => {
+                     out.say("All profiles disabled; Hostly's entries were removed from the hosts file. Run `hostly undo` to restore it.");
+                     problems.into_iter().for_each(|p| out.error(p));
+                 },
+                 Err(e) => {
+                     out.fail("Failed to disable profiles", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Auto) => {
+             let network = crate::hosts::detect_network();
+             out.note(format!(
+                 "Network: SSID {}, gateway {}",
+                 network.ssid.as_deref().unwrap_or("unknown"),
+                 network.gateway_mac.as_deref().unwrap_or("unknown")
+             ));
+             out.set("network", &network);
+             match storage::auto_activate_internal(&ctx, &network) {
+                 Ok(changed) if changed.is_empty() => out.say("No profiles to activate."),
+                 Ok(changed) => {
+                     for name in &changed {
+                         out.say(format!("Opened '{}'", name));
+                     }
+                     out.set("opened", &changed);
+                     match storage::apply_config_with_summary_internal(&ctx) {
+                         Ok(summary) => report_apply(&mut out, &summary),
+                         Err(e) => {
+                             out.fail("Failed to apply hosts", e);
+                             exit_code = 1;
+                         }
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Failed to evaluate activation rules", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Apply) => {
+             match storage::apply_config_with_summary_internal(&ctx) {
+                 Ok(summary) => report_apply(&mut out, &summary),
+                 Err(e) => {
+                     out.fail("Failed to apply hosts", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Status) => {
+             match storage::load_config_internal(&ctx) {
+                 Ok(config) => {
+                     let mode = if config.multi_select { "multi" } else { "single" };
+                     let active: Vec<&str> = config.profiles.iter().filter(|p| p.active).map(|p| p.name.as_str()).collect();
+                     let hosts_path = crate::hosts::get_hosts_path().to_string_lossy().to_string();
+
+                     // DIRTY means the live file differs from what `apply` would write
+                     let dirty = match (crate::hosts::get_system_hosts(), storage::build_pending_hosts(&ctx)) {
+                         (Ok(current), Ok(pending)) => {
+                             let normalized = |c: &str| crate::hosts::without_timestamp(
+                                 &crate::hosts::normalize_line_endings(c, crate::hosts::LineEnding::Lf),
+                             );
+                             Ok(normalized(&current) != normalized(&pending))
+                         },
+                         (Err(e), _) | (_, Err(e)) => Err(e),
+                     };
 
-             let content = match fs::read_to_string(&path) {
-                 Ok(c) => c,
+                     if cli.json {
+                         let status = serde_json::json!({
+                             "mode": mode,
+                             "active_profiles": active,
+                             "hosts_path": hosts_path,
+                             "app_dir": ctx.get_app_dir().ok().map(|d| d.to_string_lossy().to_string()),
+                             "dirty": dirty.as_ref().ok(),
+                             "error": dirty.as_ref().err(),
+                         });
+                         out.emit(&status);
+                     } else {
+                         out.say(format!("Mode:            {}", mode));
+                         out.say(format!("Active profiles: {}", if active.is_empty() { "(none)".to_string() } else { active.join(", ") }));
+                         let source = if crate::hosts::hosts_path_override().is_some() { " (HOSTLY_HOSTS_PATH)" } else { "" };
+                         out.say(format!("Hosts file:      {}{}", hosts_path, source));
+                         if let Ok(app_dir) = ctx.get_app_dir() {
+                             out.say(format!("Data dir:        {}", app_dir.display()));
+                         }
+                         match &dirty {
+                             Ok(true) => out.say("State:           DIRTY (run `hostly apply`)"),
+                             Ok(false) => out.say("State:           CLEAN"),
+                             Err(e) => out.say(format!("State:           UNKNOWN ({})", e)),
+                         }
+                     }
+                     if dirty.is_err() {
+                         exit_code = 1;
+                     }
+                 },
                  Err(e) => {
-                      eprintln!("Failed to read file: {}", e);
-                      return true;
+                     out.fail("Failed to load config", e);
+                     exit_code = 1;
                  }
+             }
+        },
+        Some(Commands::Version) => {
+             let version = crate::hosts::app_version();
+             if cli.json {
+                 out.emit(&version);
+             } else {
+                 out.say(version.to_string());
+             }
+        },
+        Some(Commands::Paths) => {
+             match storage::get_paths_internal(&ctx) {
+                 Ok(paths) if cli.json => {
+                     out.emit(&paths);
+                 },
+                 Ok(paths) => {
+                     out.say(format!("App data dir:    {}", paths.app_dir));
+                     out.say(format!("Config:          {}", paths.config_path));
+                     out.say(format!("Profiles dir:    {}", paths.profiles_dir));
+                     out.say(format!("Common config:   {}", paths.common_path));
+                     out.say(format!("Common snippets: {}", paths.common_dir));
+                     out.say(format!("Backups dir:     {}", paths.backups_dir));
+                     out.say(format!("Hosts file:      {}", paths.hosts_path));
+                 },
+                 Err(e) => {
+                     out.fail("Failed to resolve paths", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Completions { shell }) => {
+             // Complete for whichever binary is running (hostly or hostly-core)
+             let bin_name = std::env::current_exe()
+                 .ok()
+                 .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+                 .unwrap_or_else(|| "hostly".to_string());
+             let mut script = Vec::new();
+             clap_complete::generate(shell, &mut Cli::command(), bin_name.as_str(), &mut script);
+             let script = with_name_completion(shell, &bin_name, String::from_utf8_lossy(&script).into_owned());
+             if cli.json {
+                 out.set("script", script);
+             } else {
+                 print!("{}", script);
+             }
+        },
+        Some(Commands::ImportCurrent { name, keep_managed }) => {
+             match storage::import_current_hosts_internal(&ctx, name.clone(), keep_managed) {
+                 Ok(id) => {
+                     out.note(format!("Saved the current hosts file as profile '{}'.", name));
+                     out.say(id.as_str());
+                     out.set("id", &id);
+                 },
+                 Err(e) => {
+                     out.fail(format!("Failed to import current hosts as '{}'", name), e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Snapshot { name, unlocked }) => {
+             match storage::snapshot_system_hosts_internal(&ctx, name.clone(), !unlocked) {
+                 Ok(id) => {
+                     out.note(format!("Saved a snapshot of the system hosts as profile '{}'.", name));
+                     out.say(id.as_str());
+                     out.set("id", &id);
+                 },
+                 Err(e) => {
+                     out.fail(format!("Failed to snapshot the system hosts as '{}'", name), e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Create { name, from }) => {
+             match from.as_deref().map(read_input).transpose() {
+                 Ok(content) => match storage::create_profile_internal(&ctx, name.clone(), content, None, None, None) {
+                     Ok(id) => {
+                         out.note(format!("Created profile '{}'.", name));
+                         // The id alone on stdout so scripts can capture it
+                         out.say(id.as_str());
+                         out.set("id", &id);
+                     },
+                     Err(e) => {
+                         out.fail(format!("Failed to create profile '{}'", name), e);
+                         exit_code = 1;
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Failed to read input", storage::StorageError::IoError(e));
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Delete { name, force }) => {
+             let config = storage::load_config_internal(&ctx).unwrap_or_default();
+             let id = storage::resolve_profile(&ctx, &name).ok().flatten();
+             match config.profiles.iter().find(|p| Some(&p.id) == id.as_ref()) {
+                 None => {
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                     exit_code = 1;
+                 },
+                 Some(p) if p.active && !force => {
+                     out.reject("profile_active", format!("Profile '{}' is active. Close it first or pass --force.", name));
+                     exit_code = 1;
+                 },
+                 Some(p) if p.active && !confirm(&format!("Profile '{}' is active. Delete it anyway?", name), assume_yes) => {
+                     out.reject("cancelled", "Delete cancelled.");
+                     exit_code = 1;
+                 },
+                 Some(p) => {
+                     let was_active = p.active;
+                     match storage::delete_profile_internal(&ctx, &p.id) {
+                         Ok(_) => {
+                             out.say(format!("Deleted '{}'.", name));
+                             // Its entries are still in the hosts file
+                             if was_active {
+                                 if let Err(e) = reapply(&ctx, &mut out) {
+                                     out.fail("Failed to apply hosts", e);
+                                     exit_code = 1;
+                                 }
+                             }
+                         },
+                         Err(e) => {
+                             out.fail(format!("Failed to delete '{}'", name), e);
+                             exit_code = 1;
+                         }
+                     }
+                 }
+             }
+        },
+        Some(Commands::Rename { name, new_name }) => {
+             match storage::resolve_profile(&ctx, &name) {
+                 Ok(Some(id)) => match storage::rename_profile_internal(&ctx, &id, new_name.clone()) {
+                     Ok(_) => out.say(format!("Renamed '{}' to '{}'.", name, new_name)),
+                     Err(e) => {
+                         out.fail(format!("Failed to rename '{}'", name), e);
+                         exit_code = 1;
+                     }
+                 },
+                 _ => {
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Duplicate { name, new_name }) => {
+             match storage::resolve_profile(&ctx, &name) {
+                 Ok(Some(id)) => match storage::duplicate_profile_internal(&ctx, &id, new_name.clone()) {
+                     Ok(_) => out.say(format!("Duplicated '{}' as '{}'.", name, new_name)),
+                     Err(e) => {
+                         out.fail(format!("Failed to duplicate '{}'", name), e);
+                         exit_code = 1;
+                     }
+                 },
+                 _ => {
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Check { port }) => {
+             match storage::check_reachability_internal(&ctx, port) {
+                 Ok(results) => {
+                     if cli.json {
+                         out.emit(&results);
+                     } else {
+                         for r in &results {
+                             match (&r.latency_ms, &r.error) {
+                                 (Some(ms), _) => out.say(format!("OK    {:>5}ms  {} -> {} [{}]", ms, r.hostname, r.ip, r.profile_name)),
+                                 (None, err) => out.say(format!("FAIL           {} -> {} [{}]: {}", r.hostname, r.ip, r.profile_name, err.as_deref().unwrap_or("unreachable"))),
+                             }
+                         }
+                         if results.is_empty() {
+                             out.note("No active entries to check.");
+                         }
+                     }
+                     if results.iter().any(|r| !r.reachable) {
+                         exit_code = 1;
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Check failed", e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::Fmt { name, sort }) => {
+             match storage::resolve_profile(&ctx, &name) {
+                 Ok(Some(id)) => match storage::canonicalize_profile_internal(&ctx, &id, sort) {
+                     Ok(false) => out.say(format!("'{}' is already tidy.", name)),
+                     Ok(true) => {
+                         out.say(format!("Formatted '{}'.", name));
+                         let active = storage::load_config_internal(&ctx)
+                             .map(|c| c.profiles.iter().any(|p| p.id == id && p.active))
+                             .unwrap_or(false);
+                         if active {
+                             match storage::apply_config_with_summary_internal(&ctx) {
+                                 Ok(summary) => report_apply(&mut out, &summary),
+                                 Err(e) => {
+                                     out.fail("Failed to apply hosts", e);
+                                     exit_code = 1;
+                                 }
+                             }
+                         }
+                     },
+                     Err(e) => {
+                         out.fail(format!("Failed to format '{}'", name), e);
+                         exit_code = 1;
+                     }
+                 },
+                 Ok(None) => {
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                     exit_code = 1;
+                 },
+                 Err(e) => {
+                     out.fail(format!("Failed to look up '{}'", name), e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Lint { name }) => {
+             match storage::resolve_profile(&ctx, &name) {
+                 Ok(Some(id)) => match storage::read_profile_content_internal(&ctx, &id) {
+                     Ok(content) => {
+                         let warnings = crate::hosts::lint_profile(&content);
+                         if cli.json {
+                             out.emit(&warnings);
+                         } else {
+                             for warning in &warnings {
+                                 out.say(warning.to_string());
+                             }
+                         }
+                         if !warnings.is_empty() {
+                             exit_code = 1;
+                         }
+                     },
+                     Err(e) => {
+                         out.fail(format!("Failed to read '{}'", name), e);
+                         exit_code = 2;
+                     }
+                 },
+                 Ok(None) => {
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: name.clone() });
+                     exit_code = 2;
+                 },
+                 Err(e) => {
+                     out.fail(format!("Failed to look up '{}'", name), e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::Search { query }) => {
+             match storage::search_profiles_internal(&ctx, &query) {
+                 Ok(hits) if cli.json => {
+                     out.emit(&hits);
+                 },
+                 Ok(hits) => {
+                     for hit in &hits {
+                         match hit.line {
+                             Some(line) => out.say(format!("{}:{}: {}", hit.profile_name, line, hit.text)),
+                             None => out.say(format!("{}: (name matches)", hit.profile_name)),
+                         }
+                     }
+                     // grep-style: nothing found is a failure for scripts
+                     if hits.is_empty() {
+                         exit_code = 1;
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Search failed", e);
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::DiffProfiles { name_a, name_b }) => {
+             let ids = (
+                 storage::resolve_profile(&ctx, &name_a),
+                 storage::resolve_profile(&ctx, &name_b),
+             );
+             match ids {
+                 (Ok(Some(id_a)), Ok(Some(id_b))) => match storage::diff_profiles_internal(&ctx, &id_a, &id_b) {
+                     Ok(diffs) if cli.json => {
+                         out.emit(&diffs);
+                         if !diffs.is_empty() {
+                             exit_code = 1;
+                         }
+                     },
+                     Ok(diffs) if diffs.is_empty() => out.say(format!("'{}' and '{}' define the same entries.", name_a, name_b)),
+                     Ok(diffs) => {
+                         out.say(format!("--- {}", name_a));
+                         out.say(format!("+++ {}", name_b));
+                         for d in &diffs {
+                             let ip_a = d.ip_a.as_deref().unwrap_or_default();
+                             let ip_b = d.ip_b.as_deref().unwrap_or_default();
+                             match d.kind {
+                                 storage::EntryDiffKind::OnlyInA => out.say(format!("- {} {}", ip_a, d.hostname)),
+                                 storage::EntryDiffKind::OnlyInB => out.say(format!("+ {} {}", ip_b, d.hostname)),
+                                 storage::EntryDiffKind::IpChanged => out.say(format!("~ {} {} -> {}", d.hostname, ip_a, ip_b)),
+                             }
+                         }
+                         exit_code = 1;
+                     },
+                     Err(e) => {
+                         out.fail("Failed to compare profiles", e);
+                         exit_code = 2;
+                     }
+                 },
+                 (a, _) => {
+                     let missing = if matches!(a, Ok(Some(_))) { &name_b } else { &name_a };
+                     out.fail_with(storage::StorageError::ProfileNotFound { id: missing.clone() });
+                     exit_code = 2;
+                 }
+             }
+        },
+        Some(Commands::ImportUrl { url, name }) => {
+             out.say(format!("Downloading '{}' into profile '{}'...", url, name));
+             match storage::import_from_url_internal(&ctx, name.clone(), url) {
+                 Ok(id) => {
+                     out.say(format!("Imported profile '{}'.", name));
+                     let active = storage::load_config_internal(&ctx)
+                         .map(|c| c.profiles.iter().any(|p| p.id == id && p.active))
+                         .unwrap_or(false);
+                     if active {
+                         if let Err(e) = reapply(&ctx, &mut out) {
+                             out.fail("Failed to apply hosts", e);
+                             exit_code = 1;
+                         }
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Import failed", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Refresh { all, name }) => {
+             if let Some(n) = &name {
+                 let refreshed = match storage::resolve_profile(&ctx, n) {
+                     Ok(Some(id)) => storage::refresh_profile(&ctx, &id),
+                     Ok(None) => Err(format!("Profile '{}' not found", n)),
+                     Err(e) => Err(e),
+                 };
+                 match refreshed {
+                     Ok(()) => {
+                         out.say(format!("Refreshed profile '{}'.", n));
+                         out.set("refreshed", 1);
+                     },
+                     Err(e) => {
+                         out.fail("Refresh failed", e);
+                         exit_code = 1;
+                     }
+                 }
+             } else {
+                 let ids = if all {
+                     storage::load_config_internal(&ctx)
+                         .map(|c| c.profiles.into_iter().filter(|p| p.url.is_some()).map(|p| p.id).collect())
+                 } else {
+                     storage::due_refresh_ids(&ctx)
+                 };
+                 match ids.and_then(|ids| storage::refresh_profiles(&ctx, &ids).map(|summary| (summary, ids.len()))) {
+                     Ok((summary, total)) => {
+                         out.say(format!("Refreshed {} of {} profile(s).", summary.updated, total));
+                         out.set("refreshed", summary.updated);
+                         if !summary.failures.is_empty() {
+                             summary.failures.into_iter().for_each(|f| out.error(f));
+                             exit_code = 1;
+                         }
+                     },
+                     Err(e) => {
+                         out.fail("Refresh failed", e);
+                         exit_code = 1;
+                     }
+                 }
+             }
+        },
+        Some(Commands::Backup { dir, keep }) => {
+             match storage::write_backup_internal(&ctx, dir.as_deref().map(expand_path), keep) {
+                 Ok((path, problems)) => {
+                     out.say(format!("Backup written to '{}'", path.display()));
+                     out.set("path", path);
+                     problems.into_iter().for_each(|p| out.warn(p));
+                 },
+                 Err(e) => {
+                     out.fail("Backup failed", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Restore { file }) => {
+             let path = match file {
+                 Some(f) => Ok(expand_path(&f)),
+                 None => storage::get_backups_dir(&ctx)
+                     .and_then(|dir| storage::list_backups(&dir))
+                     .and_then(|b| b.last().cloned().ok_or_else(|| "No backups found".to_string())),
              };
+             let loaded = path.and_then(|path| {
+                 let json = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                 let info = storage::inspect_backup(&json).map_err(|e| format!("{} is not a Hostly backup: {}", path.display(), e))?;
+                 Ok((path, json, info))
+             });
 
-             if let Ok(count) = storage::import_switchhosts_internal(&ctx, content) {
-                 println!("Successfully migrated {} profiles from SwitchHosts backup '{}'", count, target);
-                 if let Err(e) = storage::apply_config_internal(&ctx) {
-                     eprintln!("Failed to apply hosts: {}", e);
-                 }
+             match loaded {
+                 Ok((path, json, info)) => {
+                     out.say(format!("Backup:    {}", path.display()));
+                     out.say(format!("Created:   {}", info.timestamp));
+                     out.say(format!("Profiles:  {}", info.profiles));
 
-             } else {
-                 eprintln!("Migration failed. Please check if the file is a valid SwitchHosts JSON backup.");
+                     if confirm("This replaces all current profiles and settings. Continue?", assume_yes) {
+                         match storage::import_data_internal(&ctx, json, storage::ImportMode::Replace, None).and_then(|_| reapply(&ctx, &mut out)) {
+                             Ok(_) => out.say("Backup restored and applied."),
+                             Err(e) => {
+                                 out.fail("Restore failed", e);
+                                 exit_code = 1;
+                             }
+                         }
+                     } else {
+                         out.reject("cancelled", "Restore cancelled.");
+                         exit_code = 1;
+                     }
+                 },
+                 Err(e) => {
+                     out.fail("Restore failed", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Gc) => {
+             match storage::gc_profiles(&ctx) {
+                 Ok(removed) => out.say(format!("Removed {} orphaned profile file(s).", removed)),
+                 Err(e) => {
+                     out.fail("Cleanup failed", e);
+                     exit_code = 1;
+                 }
              }
         },
-        None => return false // No subcommand, run GUI
+        Some(Commands::Repair) => {
+             match storage::repair_config(&ctx) {
+                 Ok(report) if cli.json => {
+                     out.emit(&report);
+                 }
+                 Ok(report) if !report.repaired => out.say("config.json is readable; nothing to repair."),
+                 Ok(report) => {
+                     out.say(format!("Rebuilt config.json with {} profile(s):", report.recovered.len()));
+                     for p in &report.recovered {
+                         let source = if p.from_backup { " (from config.json.bak)" } else { "" };
+                         out.say(format!("  {} [{}]{}", p.name, p.id, source));
+                     }
+                     out.say("Lost:");
+                     for item in &report.lost {
+                         out.say(format!("  - {}", item));
+                     }
+                     if let Some(broken) = &report.broken_config {
+                         out.say(format!("The unreadable config was moved to '{}'.", broken));
+                     }
+                 }
+                 Err(e) => {
+                     out.fail("Repair failed", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Relocate { dir }) => {
+             match storage::relocate_data_dir(&ctx, &expand_path(&dir)) {
+                 Ok(()) => {
+                     let new_dir = ctx.get_app_dir().map(|d| d.display().to_string()).unwrap_or_default();
+                     out.say(format!("Data directory moved to {}.", new_dir));
+                     out.set("app_dir", new_dir);
+                 }
+                 Err(e) => {
+                     out.fail("Failed to move the data directory", e);
+                     exit_code = 1;
+                 }
+             }
+        },
+        Some(Commands::Watch) => {
+             if let Err(e) = storage::watch_profiles(&ctx) {
+                 out.fail("Watch failed", e);
+                 exit_code = 1;
+             }
+        },
+        Some(Commands::CompleteNames) => {
+             if let Ok(config) = storage::load_config_internal(&ctx) {
+                 for p in config.profiles {
+                     out.say(p.name);
+                 }
+             }
+        },
+        None => return None // No subcommand, run GUI
     }
 
-    true // Command executed, exit app
+    if let (Some(dir), Ok(real)) = (&scratch, &real_app_dir) {
+        report_dry_run_changes(real, dir.path());
+    }
+    // The caller may process::exit, which skips destructors
+    drop(scratch);
+    out.finish();
+    Some(exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion_script(shell: clap_complete::Shell) -> String {
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut Cli::command(), "hostly-core", &mut script);
+        with_name_completion(shell, "hostly-core", String::from_utf8(script).unwrap())
+    }
+
+    /// Runs `hostly <args>` headless against whatever `scratch_system` set up.
+    fn run(args: &[&str]) -> i32 {
+        let cli = Cli::try_parse_from(std::iter::once("hostly").chain(args.iter().copied())).unwrap();
+        run_command(cli, storage::Context::Headless).expect("a subcommand was given")
+    }
+
+    #[test]
+    fn headless_cli_manages_profiles_and_the_hosts_file() {
+        let (_guard, dir) = crate::hosts::tests::scratch_system("127.0.0.1 localhost\n");
+        let hosts = || crate::hosts::get_system_hosts().unwrap();
+        let source = dir.path().join("api.txt");
+        fs::write(&source, "10.0.0.1 api.local\n").unwrap();
+
+        assert_eq!(run(&["create", "api", "--from", source.to_str().unwrap()]), 0);
+        assert_eq!(run(&["--dry-run", "open", "api"]), 0);
+        assert!(!hosts().contains("api.local"));
+
+        assert_eq!(run(&["open", "api"]), 0);
+        assert!(hosts().starts_with("127.0.0.1 localhost\n"));
+        assert!(hosts().contains("10.0.0.1 api.local"));
+        assert_ne!(run(&["open", "nope"]), 0);
+
+        assert_eq!(run(&["close", "api"]), 0);
+        assert!(!hosts().contains("api.local"));
+        assert_eq!(run(&["delete", "api"]), 0);
+        assert_eq!(storage::resolve_profile(&storage::Context::Headless, "api").unwrap(), None);
+    }
+
+    #[test]
+    fn completion_scripts_complete_profile_names() {
+        use clap_complete::Shell;
+
+        let bash = completion_script(Shell::Bash);
+        assert!(bash.contains("_hostly__core \"$@\"\n}\ncomplete -F _hostly__core_profile_names"));
+        assert!(bash.contains("open|close|toggle)"));
+
+        // One per name argument: open, close and toggle
+        let zsh = completion_script(Shell::Zsh);
+        assert!(zsh.starts_with("#compdef hostly-core\n\n_hostly__core_profile_names() {"));
+        assert_eq!(zsh.matches(":_hostly__core_profile_names'").count(), NAME_COMMANDS.len());
+
+        let fish = completion_script(Shell::Fish);
+        assert!(fish.ends_with("-n \"__fish_seen_subcommand_from open close toggle\" -f -a \"(hostly-core __complete-names 2>/dev/null)\"\n"));
+    }
 }
@@ -1,13 +1,36 @@
+use crate::storage::Context;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Resolved hosts file path. `HOSTLY_HOSTS_PATH` overrides the platform default,
+/// for tests, containers and non-standard setups.
+pub(crate) fn get_hosts_path() -> PathBuf {
+    hosts_path_override().unwrap_or_else(default_hosts_path)
+}
+
+pub(crate) fn hosts_path_override() -> Option<PathBuf> {
+    match std::env::var("HOSTLY_HOSTS_PATH") {
+        Ok(p) if !p.trim().is_empty() => Some(PathBuf::from(p)),
+        _ => None,
+    }
+}
+
+/// The hosts file Hostly reads and writes, with any override applied.
+#[tauri::command]
+pub fn get_hosts_path_command() -> Result<String, String> {
+    Ok(get_hosts_path().to_string_lossy().to_string())
+}
 
 #[cfg(target_os = "windows")]
-fn get_hosts_path() -> PathBuf {
+fn default_hosts_path() -> PathBuf {
     PathBuf::from("C:\\Windows\\System32\\drivers\\etc\\hosts")
 }
 
 #[cfg(not(target_os = "windows"))]
-fn get_hosts_path() -> PathBuf {
+fn default_hosts_path() -> PathBuf {
     PathBuf::from("/etc/hosts")
 }
 
@@ -17,18 +40,210 @@ pub fn get_system_hosts() -> Result<String, String> {
     fs::read_to_string(&path).map_err(|e| e.to_string())
 }
 
+/// The live system hosts as entries, one per line. Lines that aren't valid entries
+/// come back as `EntryKind::Invalid` rather than being dropped.
 #[tauri::command]
-pub fn save_system_hosts(content: String) -> Result<(), String> {
+pub fn get_system_hosts_parsed() -> Result<Vec<HostEntry>, String> {
+    let content = get_system_hosts()?;
+    // Keep `raw` free of '\r' on Windows-style files
+    Ok(parse_profile(&normalize_line_endings(&content, LineEnding::Lf)))
+}
+
+#[tauri::command]
+pub fn save_system_hosts(app: AppHandle, content: String) -> Result<(), String> {
+    let problems = save_system_hosts_internal(&Context::Tauri(&app), content)?;
+    crate::storage::emit_apply_errors(&app, &problems);
+    Ok(())
+}
+
+/// Writes the system hosts, keeping the previous content as the single-slot undo snapshot.
+/// The content is converted to the line ending the existing file already uses.
+/// Returns what went wrong without stopping the write (a failed undo snapshot).
+pub fn save_system_hosts_internal(ctx: &Context, content: String) -> Result<Vec<String>, String> {
+    let previous = get_system_hosts().ok();
+    let ending = previous.as_deref().map(detect_line_ending).unwrap_or_else(LineEnding::native);
+    let mut content = normalize_line_endings(&content, ending);
+
+    // Re-applying the same config would only bump the timestamp; keep the file as it is
+    if let Some(previous) = &previous {
+        if without_timestamp(previous) == without_timestamp(&content) {
+            content = previous.clone();
+        }
+    }
+
+    // Nothing to snapshot if the file doesn't change; keep the older, useful one
+    let mut problems = Vec::new();
+    if let Some(previous) = previous {
+        if previous != content {
+            if let Err(e) = save_undo_snapshot(ctx, &previous) {
+                problems.push(format!("Failed to save undo snapshot: {}", e));
+            }
+        }
+    }
+
+    write_system_hosts_or_preview(ctx, content)?;
+    Ok(problems)
+}
+
+/// First and last line of the region Hostly generates, so its own output can be recognized
+/// (and eventually merged into a file that also has user-managed sections).
+pub const HOSTLY_BEGIN: &str = "# HOSTLY:BEGIN";
+pub const HOSTLY_END: &str = "# HOSTLY:END";
+/// Header line that changes on every apply
+pub(crate) const GENERATED_AT: &str = "# Generated at: ";
+
+/// `content` without the generation timestamp, for telling whether anything really changed.
+pub(crate) fn without_timestamp(content: &str) -> String {
+    content.split('\n').filter(|l| !l.starts_with(GENERATED_AT)).collect::<Vec<_>>().join("\n")
+}
+
+/// Puts Hostly's generated `block` (HOSTLY_BEGIN through HOSTLY_END) into `existing`,
+/// leaving every line outside the markers as it is. Without markers the block is
+/// appended; duplicated blocks are all Hostly's and collapse into the first one.
+/// Unbalanced markers are an error, since there's no telling where our region ends.
+pub(crate) fn splice_managed_block(existing: &str, block: &str) -> Result<String, String> {
+    let existing = normalize_line_endings(existing, LineEnding::Lf);
+    let lines: Vec<&str> = existing.split('\n').collect();
+
+    // Before the markers existed Hostly owned the whole file
+    if lines.iter().map(|l| l.trim()).find(|l| !l.is_empty()) == Some("# Generated by Hostly") {
+        return Ok(block.to_string());
+    }
+
+    let mut regions = Vec::new();
+    let mut open: Option<usize> = None;
+    for (idx, line) in lines.iter().enumerate() {
+        match line.trim() {
+            HOSTLY_BEGIN => {
+                if let Some(begin) = open {
+                    return Err(format!("Unmatched '{}' at line {} of the hosts file; fix or remove it and apply again", HOSTLY_BEGIN, begin + 1));
+                }
+                open = Some(idx);
+            }
+            HOSTLY_END => match open.take() {
+                Some(begin) => regions.push((begin, idx)),
+                None => return Err(format!("Unmatched '{}' at line {} of the hosts file; fix or remove it and apply again", HOSTLY_END, idx + 1)),
+            },
+            _ => {}
+        }
+    }
+    if let Some(begin) = open {
+        return Err(format!("Unmatched '{}' at line {} of the hosts file; fix or remove it and apply again", HOSTLY_BEGIN, begin + 1));
+    }
+
+    let block = block.trim_end_matches('\n');
+    if regions.is_empty() {
+        let mut out = existing.trim_end().to_string();
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(block);
+        out.push('\n');
+        return Ok(out);
+    }
+
+    let mut out: Vec<&str> = Vec::new();
+    let mut next = 0;
+    for (n, (begin, end)) in regions.iter().enumerate() {
+        out.extend_from_slice(&lines[next..*begin]);
+        if n == 0 {
+            out.push(block);
+        }
+        next = end + 1;
+    }
+    out.extend_from_slice(&lines[next..]);
+    Ok(out.join("\n"))
+}
+
+/// `existing` with every Hostly block removed, leaving only what the user manages.
+/// A legacy fully-generated file strips down to nothing.
+pub(crate) fn strip_managed_block(existing: &str) -> Result<String, String> {
+    let rest = splice_managed_block(existing, "")?;
+    let rest = rest.trim();
+    Ok(if rest.is_empty() { String::new() } else { format!("{}\n", rest) })
+}
+
+/// The first HOSTLY_BEGIN..HOSTLY_END block of `content`, markers included.
+pub(crate) fn managed_region(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let begin = lines.iter().position(|l| l.trim() == HOSTLY_BEGIN)?;
+    let end = begin + lines[begin..].iter().position(|l| l.trim() == HOSTLY_END)?;
+    Some(lines[begin..=end].join("\n"))
+}
+
+/// In dry-run mode prints what would be written instead of touching the system file.
+fn write_system_hosts_or_preview(ctx: &Context, content: String) -> Result<(), String> {
+    if !ctx.is_dry_run() {
+        return write_system_hosts(content);
+    }
+
+    let current = get_system_hosts().unwrap_or_default();
+    let label = get_hosts_path().to_string_lossy().to_string();
+    let diff = unified_diff(&current, &content, &label, &format!("{} (dry run)", label));
+    // stderr, so stdout keeps only the command's own result
+    if diff.is_empty() {
+        eprintln!("[dry-run] {} would not change.", label);
+    } else {
+        eprintln!("[dry-run] Would write {}:", label);
+        eprint!("{}", diff);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn native() -> Self {
+        if cfg!(target_os = "windows") {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+}
+
+/// The dominant line ending of `content`, or the platform default if it has no line breaks
+/// (or an even split).
+pub(crate) fn detect_line_ending(content: &str) -> LineEnding {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    if crlf > lf {
+        LineEnding::CrLf
+    } else if lf > crlf {
+        LineEnding::Lf
+    } else {
+        LineEnding::native()
+    }
+}
+
+pub(crate) fn normalize_line_endings(content: &str, ending: LineEnding) -> String {
+    let unified = content.replace("\r\n", "\n");
+    match ending {
+        LineEnding::Lf => unified,
+        LineEnding::CrLf => unified.replace('\n', "\r\n"),
+    }
+}
+
+fn write_system_hosts(content: String) -> Result<(), String> {
     let path = get_hosts_path();
     
     // Attempt normal write first
-    match fs::write(&path, &content) {
+    #[cfg(target_os = "windows")]
+    let result = with_readonly_cleared(&path, || write_atomic(&path, &content));
+    #[cfg(not(target_os = "windows"))]
+    let result = write_atomic(&path, &content);
+
+    match result {
         Ok(_) => Ok(()),
         Err(e) => {
             #[cfg(target_os = "macos")]
             {
                 let direct_err = e.to_string();
-                println!("Direct write failed: {}. Attempting elevation...", direct_err);
+                eprintln!("Direct write failed: {}. Attempting elevation...", direct_err);
                 
                 // Try elevation
                 match save_hosts_elevated_macos(&content) {
@@ -46,6 +261,169 @@ pub fn save_system_hosts(content: String) -> Result<(), String> {
     }
 }
 
+/// Security software sometimes marks the hosts file read-only, which makes even an
+/// elevated write fail with "Access is denied". Clears the flag around `write` and
+/// sets it again afterwards, whether or not the write succeeded.
+#[cfg(target_os = "windows")]
+fn with_readonly_cleared<T>(path: &Path, write: impl FnOnce() -> std::io::Result<T>) -> Result<T, String> {
+    let readonly = fs::metadata(path).map(|m| m.permissions().readonly()).unwrap_or(false);
+    if !readonly {
+        return write().map_err(|e| e.to_string());
+    }
+
+    let set_readonly = |value: bool| -> std::io::Result<()> {
+        let mut perms = fs::metadata(path)?.permissions();
+        perms.set_readonly(value);
+        fs::set_permissions(path, perms)
+    };
+    set_readonly(false)
+        .map_err(|e| format!("Failed to clear the read-only attribute on '{}': {}", path.display(), e))?;
+
+    let result = write().map_err(|e| e.to_string());
+    if let Err(e) = set_readonly(true) {
+        eprintln!("Failed to restore the read-only attribute on '{}': {}", path.display(), e);
+    }
+    result
+}
+
+fn get_undo_snapshot_path(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join("last_applied.bak"))
+}
+
+fn save_undo_snapshot(ctx: &Context, previous: &str) -> Result<(), String> {
+    let path = get_undo_snapshot_path(ctx)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    write_atomic(&path, previous).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn undo_apply(app: AppHandle) -> Result<(), String> {
+    let problems = undo_last_apply(&Context::Tauri(&app))?;
+    crate::storage::emit_apply_errors(&app, &problems);
+    Ok(())
+}
+
+/// Restores the system hosts to what it was right before the last write.
+/// Returns what failed after the restore (flushing the DNS cache).
+pub fn undo_last_apply(ctx: &Context) -> Result<Vec<String>, String> {
+    let path = get_undo_snapshot_path(ctx)?;
+    if !path.exists() {
+        return Err("Nothing to undo: no previous hosts snapshot found".to_string());
+    }
+
+    let snapshot = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    write_system_hosts_or_preview(ctx, snapshot)?;
+    // The slot is consumed; a second undo has nothing to go back to
+    let _ = fs::remove_file(&path);
+
+    let mut problems = Vec::new();
+    if !ctx.is_dry_run() {
+        if let Err(e) = flush_dns() {
+            problems.push(format!("Failed to flush DNS cache: {}", e));
+        }
+    }
+    Ok(problems)
+}
+
+#[tauri::command]
+pub fn disable_all_command(app: AppHandle) -> Result<(), String> {
+    let problems = disable_all(&Context::Tauri(&app))?;
+    crate::storage::emit_apply_errors(&app, &problems);
+    Ok(())
+}
+
+/// Recovery action: removes Hostly's block from the system hosts and marks every profile
+/// inactive. Lines outside the markers stay; if nothing is left (a legacy fully-generated
+/// file, or no file at all) a bare localhost baseline is written instead. The replaced
+/// file becomes the undo snapshot, so `undo` brings it back; re-activating profiles
+/// rebuilds it from the config instead. Returns what failed after the write.
+pub fn disable_all(ctx: &Context) -> Result<Vec<String>, String> {
+    let rest = strip_managed_block(&get_system_hosts().unwrap_or_default())?;
+    let content = if rest.is_empty() { baseline_hosts() } else { rest };
+
+    // Hosts first: if the write isn't permitted the config stays as it was
+    let mut problems = save_system_hosts_internal(ctx, content)?;
+    crate::storage::set_active_profiles_internal(ctx, &[])?;
+
+    if !ctx.is_dry_run() {
+        if let Err(e) = flush_dns() {
+            problems.push(format!("Failed to flush DNS cache: {}", e));
+        }
+    }
+    Ok(problems)
+}
+
+/// A minimal hosts file close to what the OS ships with.
+fn baseline_hosts() -> String {
+    let mut content = String::new();
+    if cfg!(target_os = "windows") {
+        content.push_str(concat!(
+            "# Copyright (c) 1993-2009 Microsoft Corp.\n",
+            "#\n",
+            "# This is a sample HOSTS file used by Microsoft TCP/IP for Windows.\n",
+            "#\n",
+            "# This file contains the mappings of IP addresses to host names. Each\n",
+            "# entry should be kept on an individual line. The IP address should\n",
+            "# be placed in the first column followed by the corresponding host name.\n",
+            "# The IP address and the host name should be separated by at least one\n",
+            "# space.\n",
+            "#\n",
+            "# Additionally, comments (such as these) may be inserted on individual\n",
+            "# lines or following the machine name denoted by a '#' symbol.\n",
+            "#\n",
+            "# For example:\n",
+            "#\n",
+            "#      102.54.94.97     rhino.acme.com          # source server\n",
+            "#       38.25.63.10     x.acme.com              # x client host\n",
+            "\n",
+        ));
+    }
+    content.push_str("127.0.0.1 localhost\n");
+    if cfg!(target_os = "macos") {
+        content.push_str("255.255.255.255 broadcasthost\n");
+    }
+    content.push_str("::1 localhost\n");
+    content
+}
+
+/// Writes `content` to a temp file next to `path` and renames it over the target.
+/// The rename is atomic on both Windows and Unix, so a crash or a full disk never
+/// leaves a half-written file behind. The temp file is removed on any failure.
+pub(crate) fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    write_atomic_with(path, |file| file.write_all(content.as_bytes()))
+}
+
+/// `write_atomic` with the temp file filled by `fill`, which tests use to fail mid-write.
+fn write_atomic_with(path: &Path, fill: impl FnOnce(&mut fs::File) -> std::io::Result<()>) -> std::io::Result<()> {
+    // Same directory keeps the rename on one filesystem
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    let mut temp_file = tempfile::NamedTempFile::new_in(dir)?;
+    fill(temp_file.as_file_mut())?;
+    temp_file.as_file().sync_all()?;
+
+    // Temp files are created owner-only; keep the target readable like before
+    match fs::metadata(path) {
+        Ok(meta) => temp_file.as_file().set_permissions(meta.permissions())?,
+        Err(_) => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                temp_file.as_file().set_permissions(fs::Permissions::from_mode(0o644))?;
+            }
+        }
+    }
+
+    temp_file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
 #[cfg(target_os = "macos")]
 fn save_hosts_elevated_macos(content: &str) -> Result<(), String> {
     use std::io::Write;
@@ -62,11 +440,11 @@ fn save_hosts_elevated_macos(content: &str) -> Result<(), String> {
     
     let temp_path = temp_file.path().to_string_lossy().to_string();
 
-    // Move temp file to /etc/hosts using authentication
-    let script = format!(
-        "do shell script \"mv -f '{}' /etc/hosts && chmod 644 /etc/hosts\" with administrator privileges",
-        temp_path
-    );
+    // Move temp file to the hosts path using authentication. HOSTLY_HOSTS_PATH can be
+    // anything, so both paths are quoted for the shell and then for AppleScript
+    let hosts_path = shell_quote(&get_hosts_path().to_string_lossy());
+    let command = format!("mv -f {} {} && chmod 644 {}", shell_quote(&temp_path), hosts_path, hosts_path);
+    let script = format!("do shell script {} with administrator privileges", applescript_string(&command));
 
     let output = std::process::Command::new("/usr/bin/osascript")
         .arg("-e")
@@ -81,41 +459,1478 @@ fn save_hosts_elevated_macos(content: &str) -> Result<(), String> {
     }
 }
 
+/// Single-quotes `s` for /bin/sh, so spaces, quotes and `$` stay literal.
+#[cfg(any(target_os = "macos", test))]
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// `s` as an AppleScript string literal.
+#[cfg(any(target_os = "macos", test))]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 #[tauri::command]
-pub fn check_write_permission() -> Result<bool, String> {
-    let path = get_hosts_path();
-    // Try to open the file in append mode. This checks if we have write permissions 
-    // without actually modifying or truncating the file.
-    let result = std::fs::OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(&path);
-        
-    Ok(result.is_ok())
+pub fn diff_config(app: AppHandle) -> Result<String, String> {
+    diff_pending(&Context::Tauri(&app))
+}
+
+/// Unified diff between the live system hosts and what `apply_config` would write.
+/// Returns an empty string when there is nothing to apply.
+pub fn diff_pending(ctx: &Context) -> Result<String, String> {
+    let current = get_system_hosts()?;
+    let pending = crate::storage::build_pending_hosts(ctx)?;
+    Ok(unified_diff(&without_timestamp(&current), &without_timestamp(&pending), "system hosts", "pending"))
 }
 
+enum DiffOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// Past this many table cells (about 32 MB) the changed middle is shown as one
+/// replaced block instead; hosts blocklists with 100k+ lines would otherwise need gigabytes.
+const DIFF_TABLE_LIMIT: usize = 4_000_000;
+
+/// Line-based LCS diff. Common prefix/suffix are trimmed first so large,
+/// mostly identical files stay cheap.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffOp<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = old[..prefix].iter().map(|l| DiffOp::Equal(l)).collect();
+    if a.len().saturating_mul(b.len()) > DIFF_TABLE_LIMIT {
+        ops.extend(a.iter().map(|l| DiffOp::Delete(l)));
+        ops.extend(b.iter().map(|l| DiffOp::Insert(l)));
+        ops.extend(old[old.len() - suffix..].iter().map(|l| DiffOp::Equal(l)));
+        return ops;
+    }
+
+    // table[i][j] = LCS length of a[i..] and b[j..]
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            ops.push(DiffOp::Equal(a[i]));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            ops.push(DiffOp::Delete(a[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[i..].iter().map(|l| DiffOp::Delete(l)));
+    ops.extend(b[j..].iter().map(|l| DiffOp::Insert(l)));
+    ops.extend(old[old.len() - suffix..].iter().map(|l| DiffOp::Equal(l)));
+    ops
+}
+
+/// Renders a unified diff (3 lines of context) or an empty string if the inputs match.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(_)))
+        .map(|(idx, _)| idx)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Line positions before each op, for hunk headers
+    let mut old_pos = Vec::with_capacity(ops.len() + 1);
+    let mut new_pos = Vec::with_capacity(ops.len() + 1);
+    let (mut o, mut n) = (0, 0);
+    for op in &ops {
+        old_pos.push(o);
+        new_pos.push(n);
+        match op {
+            DiffOp::Equal(_) => {
+                o += 1;
+                n += 1;
+            }
+            DiffOp::Delete(_) => o += 1,
+            DiffOp::Insert(_) => n += 1,
+        }
+    }
+    old_pos.push(o);
+    new_pos.push(n);
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut end = changes[k];
+        k += 1;
+        // Merge changes whose context windows touch
+        while k < changes.len() && changes[k] - end <= 2 * CONTEXT {
+            end = changes[k];
+            k += 1;
+        }
+        let stop = (end + CONTEXT + 1).min(ops.len());
+
+        let old_count = old_pos[stop] - old_pos[start];
+        let new_count = new_pos[stop] - new_pos[start];
+        let old_start = if old_count == 0 { old_pos[start] } else { old_pos[start] + 1 };
+        let new_start = if new_count == 0 { new_pos[start] } else { new_pos[start] + 1 };
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_count, new_start, new_count));
+
+        for op in &ops[start..stop] {
+            match op {
+                DiffOp::Equal(l) => out.push_str(&format!(" {}\n", l)),
+                DiffOp::Delete(l) => out.push_str(&format!("-{}\n", l)),
+                DiffOp::Insert(l) => out.push_str(&format!("+{}\n", l)),
+            }
+        }
+    }
+    out
+}
+
+/// A single invalid line found by `validate_hosts`.
+#[derive(Debug, Serialize, Clone)]
+pub struct HostsError {
+    /// 1-based line number within the validated content
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for HostsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: '{}' - {}", self.line, self.text, self.reason)
+    }
+}
+
+/// Runs `validate_hosts` on unsaved content, e.g. for live validation in the editor.
+/// Touches no files; an empty list means the content is valid.
 #[tauri::command]
-pub fn hostly_open_url(url: String) -> Result<(), String> {
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("cmd")
-            .args(&["/C", "start", &url])
-            .spawn()
-            .map_err(|e| e.to_string())?;
+pub fn validate_content(content: String) -> Result<Vec<HostsError>, String> {
+    Ok(validate_hosts(&content).err().unwrap_or_default())
+}
+
+/// Checks every non-comment line has a valid IPv4/IPv6 address followed by at least one hostname.
+pub fn validate_hosts(content: &str) -> Result<(), Vec<HostsError>> {
+    validate_lines(content, true)
+}
+
+/// `validate_hosts`, optionally refusing `${NAME}` in place of the address, as output
+/// that has already been through `substitute_variables` must.
+fn validate_lines(content: &str, allow_variables: bool) -> Result<(), Vec<HostsError>> {
+    let mut errors = Vec::new();
+
+    for (idx, raw) in content.lines().enumerate() {
+        // Everything after '#' is a comment
+        let data = raw.split('#').next().unwrap_or("").trim();
+        if data.is_empty() {
+            continue;
+        }
+
+        let mut parts = data.split_whitespace();
+        let ip = parts.next().unwrap_or("");
+        let reason = if let Some(directive) = parse_directive(data) {
+            directive.err()
+        } else if !(is_valid_ip(ip) || allow_variables && is_variable_ref(ip)) {
+            Some(format!("invalid IP address '{}'", ip))
+        } else if parts.next().is_none() {
+            Some("missing hostname".to_string())
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            errors.push(HostsError {
+                line: idx + 1,
+                text: raw.trim().to_string(),
+                reason,
+            });
+        }
     }
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&url)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
     }
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&url)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+}
+
+/// Parses a Hostly directive line (one starting with '@') into the IP and concrete
+/// hostnames it stands for. None when the line isn't a directive at all.
+///
+/// The only directive so far is `@wildcard <ip> *.<domain> <name> [<name>...]`, which
+/// maps `<name>.<domain>` to `<ip>` for every name, since hosts files have no wildcards.
+fn parse_directive(line: &str) -> Option<Result<(String, Vec<String>), String>> {
+    let data = line.split('#').next().unwrap_or("").trim();
+    if !data.starts_with('@') {
+        return None;
+    }
+
+    let mut parts = data.split_whitespace();
+    let directive = parts.next().unwrap_or("");
+    if directive != "@wildcard" {
+        return Some(Err(format!("unknown directive '{}'", directive)));
+    }
+
+    let ip = match parts.next() {
+        Some(ip) if is_valid_ip(ip) || is_variable_ref(ip) => ip,
+        Some(ip) => return Some(Err(format!("invalid IP address '{}'", ip))),
+        None => return Some(Err("@wildcard needs an IP, a '*.<domain>' pattern and names".to_string())),
+    };
+    let domain = match parts.next().and_then(|p| p.strip_prefix("*.")) {
+        Some(d) if !d.is_empty() && !d.contains('*') => d,
+        _ => return Some(Err("@wildcard pattern must look like '*.<domain>'".to_string())),
+    };
+
+    let names: Vec<&str> = parts.collect();
+    if names.is_empty() {
+        return Some(Err("@wildcard lists no names to expand".to_string()));
+    }
+    if let Some(bad) = names.iter().find(|n| n.contains('*') || n.starts_with('.') || n.ends_with('.')) {
+        return Some(Err(format!("invalid name '{}' in @wildcard", bad)));
+    }
+
+    let hostnames = names.iter().map(|n| format!("{}.{}", n, domain)).collect();
+    Some(Ok((ip.to_string(), hostnames)))
+}
+
+/// `${NAME}` standing in for a whole field, which `substitute_variables` fills in at merge time.
+fn is_variable_ref(token: &str) -> bool {
+    token.strip_prefix("${").and_then(|t| t.strip_suffix('}')).is_some_and(is_variable_name)
+}
+
+/// Letters, digits and underscores, not starting with a digit, like shell variables.
+pub fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A variable fills exactly one field of a line, so its value must be a single token:
+/// no whitespace (which would add fields or lines) and no '#' (which would start a comment).
+pub fn check_variable_value(name: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '#') {
+        return Err(format!("value of '{}' must be a single word without spaces or '#', got '{}'", name, value));
     }
     Ok(())
 }
+
+/// Replaces `${NAME}` with `lookup(NAME)` everywhere except in comments; `$${` is a
+/// literal `${`. Values go in as-is and are never expanded again, and references can't
+/// be nested. Every line that had a reference must still be a valid entry afterwards.
+/// Fails with the line numbers of undefined or malformed references and bad values.
+pub fn substitute_variables(content: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, Vec<HostsError>> {
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, raw) in content.split('\n').enumerate() {
+        // Comments are left alone so notes can mention ${VAR} freely
+        let (data, comment) = raw.split_at(raw.find('#').unwrap_or(raw.len()));
+        if !data.contains('$') {
+            output.push(raw.to_string());
+            continue;
+        }
+        let expanded = substitute_line(data, &lookup).and_then(|data| match validate_lines(&data, false) {
+            Ok(()) => Ok(data),
+            Err(problems) => Err(format!("'{}' after substitution: {}", data.trim(), problems[0].reason)),
+        });
+        match expanded {
+            Ok(data) => output.push(format!("{}{}", data, comment)),
+            Err(reason) => errors.push(HostsError {
+                line: idx + 1,
+                text: raw.trim().to_string(),
+                reason,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(output.join("\n"))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Every name referenced as `${NAME}` outside comments, whether or not it's defined.
+pub fn variable_references(content: &str) -> std::collections::BTreeSet<String> {
+    let names = std::cell::RefCell::new(std::collections::BTreeSet::new());
+    let _ = substitute_variables(content, |name| {
+        names.borrow_mut().insert(name.to_string());
+        Some("_".to_string())
+    });
+    names.into_inner()
+}
+
+fn substitute_line(data: &str, lookup: &impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = data;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(tail) = after.strip_prefix("${") {
+            out.push_str("${");
+            rest = tail;
+            continue;
+        }
+        let Some(body) = after.strip_prefix('{') else {
+            // A lone '$' isn't a reference
+            out.push('$');
+            rest = after;
+            continue;
+        };
+
+        let end = body.find('}').ok_or_else(|| "unclosed '${'".to_string())?;
+        let name = &body[..end];
+        if !is_variable_name(name) {
+            return Err(format!("invalid variable name '{}'", name));
+        }
+        let value = lookup(name).ok_or_else(|| format!("undefined variable '{}'", name))?;
+        check_variable_value(name, &value)?;
+        out.push_str(&value);
+        rest = &body[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Replaces each directive with one `ip hostname` line per expanded name, keeping the
+/// directive above them as a comment. Fails with the line numbers of malformed directives.
+pub fn expand_directives(content: &str) -> Result<String, Vec<HostsError>> {
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+
+    for (idx, raw) in content.split('\n').enumerate() {
+        match parse_directive(raw) {
+            None => output.push(raw.to_string()),
+            Some(Ok((ip, hostnames))) => {
+                output.push(format!("# {}", raw.trim()));
+                output.extend(hostnames.into_iter().map(|h| format!("{} {}", ip, h)));
+            }
+            Some(Err(reason)) => errors.push(HostsError {
+                line: idx + 1,
+                text: raw.trim().to_string(),
+                reason,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(output.join("\n"))
+    } else {
+        Err(errors)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// `<ip> <hostnames...>`, possibly commented out
+    Host,
+    /// A standalone comment line
+    Comment,
+    Blank,
+    /// An uncommented line that isn't a valid entry
+    Invalid,
+}
+
+/// One line of a profile. Every line maps to exactly one entry, so
+/// `parse_profile` -> `serialize_profile` keeps blank lines and comments in place.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HostEntry {
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub ip: String,
+    #[serde(default)]
+    pub hostnames: Vec<String>,
+    /// False when the entry is commented out (`# <ip> <hostnames>`)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Trailing `# ...` text for host lines, the text itself for comment lines
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Original line text, re-emitted verbatim while the entry is unchanged
+    #[serde(default)]
+    pub raw: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+pub fn parse_profile(content: &str) -> Vec<HostEntry> {
+    // split('\n') rather than lines(): a trailing newline becomes a final blank entry,
+    // which makes the round-trip exact
+    content.split('\n').map(parse_line).collect()
+}
+
+pub fn serialize_profile(entries: &[HostEntry]) -> String {
+    entries.iter().map(serialize_entry).collect::<Vec<_>>().join("\n")
+}
+
+/// Likely mistakes in a profile that shouldn't block saving it: invalid lines, the
+/// `lint_hosts` rules, and hostnames defined by more than one enabled entry.
+/// Line numbers are 1-based.
+pub fn lint_profile(content: &str) -> Vec<String> {
+    let mut warnings: Vec<String> = match validate_hosts(content) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+    };
+    warnings.extend(lint_hosts(content).iter().map(|l| l.to_string()));
+
+    // hostname -> (line, ip) in order of appearance
+    let mut seen: std::collections::BTreeMap<String, Vec<(usize, String)>> = std::collections::BTreeMap::new();
+    for (idx, entry) in parse_profile(content).iter().enumerate() {
+        if entry.kind == EntryKind::Host && entry.enabled {
+            for host in &entry.hostnames {
+                seen.entry(host.to_lowercase()).or_default().push((idx + 1, entry.ip.clone()));
+            }
+        }
+    }
+
+    for (host, defs) in seen.iter().filter(|(_, d)| d.len() > 1) {
+        let lines: Vec<String> = defs.iter().map(|(line, _)| line.to_string()).collect();
+        let mut ips: Vec<&str> = Vec::new();
+        for (_, ip) in defs {
+            if !ips.contains(&ip.as_str()) {
+                ips.push(ip);
+            }
+        }
+        let detail = if ips.len() > 1 { format!("different IPs: {}", ips.join(", ")) } else { "same IP".to_string() };
+        warnings.push(format!("'{}' is defined more than once (lines {}; {})", host, lines.join(", "), detail));
+    }
+
+    warnings
+}
+
+/// Rules checked by `lint_hosts`. A profile opts out of some with a comment line such as
+/// `# @lint-allow blackhole loopback_duplicate`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    /// `localhost` pointed somewhere other than the loopback address
+    LocalhostRedefined,
+    /// A public hostname sent to 0.0.0.0 / loopback, which silently breaks it
+    Blackhole,
+    /// `localhost` -> loopback again, which the system hosts already has
+    LoopbackDuplicate,
+}
+
+impl LintRule {
+    const ALL: [LintRule; 3] = [LintRule::LocalhostRedefined, LintRule::Blackhole, LintRule::LoopbackDuplicate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LintRule::LocalhostRedefined => "localhost_redefined",
+            LintRule::Blackhole => "blackhole",
+            LintRule::LoopbackDuplicate => "loopback_duplicate",
+        }
+    }
+}
+
+/// A suspicious (but valid) entry. These are warnings and never block an apply.
+#[derive(Debug, Serialize, Clone)]
+pub struct Lint {
+    pub rule: LintRule,
+    /// 1-based line number
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {} [{}]", self.line, self.message, self.rule.name())
+    }
+}
+
+const LOCALHOST_NAMES: [&str; 2] = ["localhost", "localhost.localdomain"];
+/// Suffixes that never resolve publicly, so mapping them to loopback is just local development
+const PRIVATE_TLDS: [&str; 11] = ["localhost", "local", "localdomain", "test", "example", "invalid", "internal", "intranet", "lan", "home", "corp"];
+
+/// Checks enabled entries (and `@wildcard` expansions) for the `LintRule` mistakes,
+/// minus the rules the content allows with `# @lint-allow`.
+pub fn lint_hosts(content: &str) -> Vec<Lint> {
+    let mut allowed: Vec<LintRule> = Vec::new();
+    for line in content.lines() {
+        let Some(rules) = line.trim().strip_prefix('#').and_then(|c| c.trim().strip_prefix("@lint-allow")) else {
+            continue;
+        };
+        for name in rules.split(|c: char| c == ',' || c.is_whitespace()).filter(|n| !n.is_empty()) {
+            allowed.extend(LintRule::ALL.iter().filter(|r| r.name() == name));
+        }
+    }
+
+    let mut lints = Vec::new();
+    for (idx, raw) in content.split('\n').enumerate() {
+        let (ip, hostnames) = match parse_directive(raw) {
+            Some(Ok(expanded)) => expanded,
+            Some(Err(_)) => continue,
+            None => match split_entry_line(raw) {
+                Some((ip, hosts)) => (ip.to_string(), hosts.into_iter().map(|h| h.to_string()).collect()),
+                None => continue,
+            },
+        };
+        let Ok(addr) = ip.split('%').next().unwrap_or("").parse::<IpAddr>() else {
+            continue;
+        };
+
+        let is_localhost = |h: &&String| LOCALHOST_NAMES.contains(&h.to_lowercase().as_str());
+        let localhost: Vec<&String> = hostnames.iter().filter(is_localhost).collect();
+        let public: Vec<&String> = hostnames
+            .iter()
+            .filter(|h| !is_localhost(h))
+            .filter(|h| match h.to_lowercase().rsplit_once('.') {
+                Some((_, tld)) => !PRIVATE_TLDS.contains(&tld),
+                None => false,
+            })
+            .collect();
+
+        let mut push = |rule: LintRule, message: String| {
+            if !allowed.contains(&rule) {
+                lints.push(Lint { rule, line: idx + 1, message });
+            }
+        };
+        let join = |hosts: &[&String]| hosts.iter().map(|h| h.as_str()).collect::<Vec<_>>().join(", ");
+
+        if !localhost.is_empty() {
+            if addr.is_loopback() {
+                push(LintRule::LoopbackDuplicate, format!("{} -> {} is already in the system hosts file", join(&localhost), ip));
+            } else {
+                push(LintRule::LocalhostRedefined, format!("{} mapped to {}; local tools expect the loopback address", join(&localhost), ip));
+            }
+        }
+        if !public.is_empty() && (addr.is_unspecified() || addr.is_loopback()) {
+            push(LintRule::Blackhole, format!("public hostname {} sent to {} and will be unreachable", join(&public), ip));
+        }
+    }
+    lints
+}
+
+/// Canonical form of a host entry: lowercase hostnames, the shortest IP notation
+/// (`0:0:0:0:0:0:0:1` -> `::1`) and single spaces. Other lines come back unchanged.
+pub fn normalize_entry(entry: &HostEntry) -> HostEntry {
+    if entry.kind != EntryKind::Host {
+        return entry.clone();
+    }
+
+    let (addr, zone) = match entry.ip.split_once('%') {
+        Some((a, z)) => (a, Some(z)),
+        None => (entry.ip.as_str(), None),
+    };
+    let mut ip = addr.parse::<IpAddr>().map(|a| a.to_string()).unwrap_or_else(|_| addr.to_string());
+    if let Some(zone) = zone {
+        ip = format!("{}%{}", ip, zone);
+    }
+
+    HostEntry {
+        ip,
+        hostnames: entry.hostnames.iter().map(|h| h.to_lowercase()).collect(),
+        // Dropping the original text makes serialize_entry emit the canonical spacing
+        raw: String::new(),
+        ..entry.clone()
+    }
+}
+
+/// Applies `normalize_entry` to every line of a profile.
+pub fn normalize_profile(content: &str) -> String {
+    let entries: Vec<HostEntry> = parse_profile(content).iter().map(normalize_entry).collect();
+    serialize_profile(&entries)
+}
+
+/// A tidied copy of a profile: normalized entries, each `ip hostname` pair kept once
+/// (enabled and disabled entries deduplicated separately), at most one blank line in a
+/// row and none at either end. Comments, invalid lines and directives stay where they are.
+/// With `sort`, each run of consecutive entries is ordered by hostname, so comments
+/// heading a block keep heading it.
+pub fn canonicalize_content(content: &str, sort: bool) -> String {
+    let mut seen: std::collections::HashSet<(bool, String, String)> = std::collections::HashSet::new();
+    let mut lines: Vec<HostEntry> = Vec::new();
+
+    for entry in parse_profile(content) {
+        match entry.kind {
+            EntryKind::Blank => {
+                if lines.last().is_some_and(|l| l.kind != EntryKind::Blank) {
+                    lines.push(entry);
+                }
+            }
+            EntryKind::Host => {
+                let mut entry = normalize_entry(&entry);
+                let enabled = entry.enabled;
+                let ip = entry.ip.clone();
+                entry.hostnames.retain(|h| seen.insert((enabled, ip.clone(), h.clone())));
+                if !entry.hostnames.is_empty() {
+                    lines.push(entry);
+                }
+            }
+            EntryKind::Comment | EntryKind::Invalid => lines.push(entry),
+        }
+    }
+    while lines.last().is_some_and(|l| l.kind == EntryKind::Blank) {
+        lines.pop();
+    }
+
+    if sort {
+        let mut start = 0;
+        while start < lines.len() {
+            let len = lines[start..].iter().take_while(|l| l.kind == EntryKind::Host).count();
+            lines[start..start + len].sort_by(|a, b| (&a.hostnames, &a.ip).cmp(&(&b.hostnames, &b.ip)));
+            start += len.max(1);
+        }
+    }
+
+    let mut out = serialize_profile(&lines);
+    out.push('\n');
+    out
+}
+
+fn parse_line(line: &str) -> HostEntry {
+    let trimmed = line.trim();
+    let mut entry = HostEntry {
+        kind: EntryKind::Blank,
+        ip: String::new(),
+        hostnames: Vec::new(),
+        enabled: true,
+        comment: None,
+        raw: line.to_string(),
+    };
+
+    if trimmed.is_empty() {
+        return entry;
+    }
+
+    if let Some(rest) = trimmed.strip_prefix('#') {
+        // "# 127.0.0.1 foo" is a disabled entry, anything else is a plain comment
+        if let Some((ip, hostnames, comment)) = parse_host_fields(rest) {
+            entry.kind = EntryKind::Host;
+            entry.ip = ip;
+            entry.hostnames = hostnames;
+            entry.enabled = false;
+            entry.comment = comment;
+        } else {
+            entry.kind = EntryKind::Comment;
+            entry.comment = Some(rest.trim().to_string());
+        }
+        return entry;
+    }
+
+    match parse_host_fields(trimmed) {
+        Some((ip, hostnames, comment)) => {
+            entry.kind = EntryKind::Host;
+            entry.ip = ip;
+            entry.hostnames = hostnames;
+            entry.comment = comment;
+        }
+        None => entry.kind = EntryKind::Invalid,
+    }
+    entry
+}
+
+fn parse_host_fields(text: &str) -> Option<(String, Vec<String>, Option<String>)> {
+    let (data, comment) = match text.split_once('#') {
+        Some((d, c)) => (d, Some(c.trim().to_string()).filter(|c| !c.is_empty())),
+        None => (text, None),
+    };
+
+    let mut parts = data.split_whitespace();
+    let ip = parts.next()?;
+    if !is_valid_ip(ip) {
+        return None;
+    }
+    let hostnames: Vec<String> = parts.map(|h| h.to_string()).collect();
+    if hostnames.is_empty() {
+        return None;
+    }
+    Some((ip.to_string(), hostnames, comment))
+}
+
+fn serialize_entry(entry: &HostEntry) -> String {
+    // Untouched lines keep their exact original spacing
+    if parse_line(&entry.raw) == *entry {
+        return entry.raw.clone();
+    }
+
+    match entry.kind {
+        EntryKind::Blank => String::new(),
+        EntryKind::Invalid => entry.raw.clone(),
+        EntryKind::Comment => format!("# {}", entry.comment.as_deref().unwrap_or("")),
+        EntryKind::Host => {
+            let mut line = format!("{} {}", entry.ip, entry.hostnames.join(" "));
+            if let Some(c) = &entry.comment {
+                line.push_str(&format!(" # {}", c));
+            }
+            if entry.enabled {
+                line
+            } else {
+                format!("# {}", line)
+            }
+        }
+    }
+}
+
+/// Splits an active hosts line into its IP and hostnames, ignoring any trailing comment.
+/// Returns `None` for blank lines, comments, and lines without a hostname.
+pub(crate) fn split_entry_line(line: &str) -> Option<(&str, Vec<&str>)> {
+    let data = line.split('#').next().unwrap_or("").trim();
+    let mut parts = data.split_whitespace();
+    let ip = parts.next()?;
+    let hostnames: Vec<&str> = parts.collect();
+    if hostnames.is_empty() {
+        None
+    } else {
+        Some((ip, hostnames))
+    }
+}
+
+/// What two entries must share to compete: the hostname and the address family.
+/// An IPv4 and an IPv6 line for the same host complement each other.
+pub(crate) fn entry_key(ip: &str, host: &str) -> (String, bool) {
+    (host.to_lowercase(), ip.contains(':'))
+}
+
+pub(crate) fn is_valid_ip(ip: &str) -> bool {
+    // Link-local IPv6 may carry a zone id (fe80::1%lo0)
+    let addr = ip.split('%').next().unwrap_or("");
+    addr.parse::<IpAddr>().is_ok()
+}
+
+/// Result of probing whether the hosts file can be written without elevation.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum WritePermission {
+    Writable,
+    NeedsElevation,
+    /// The hosts file doesn't exist, but its directory is writable so applying will create it
+    FileMissing,
+    OtherError(String),
+}
+
+#[tauri::command]
+pub fn check_write_permission() -> Result<WritePermission, String> {
+    let path = get_hosts_path();
+    // `write_atomic` replaces the file through a temp file next to it, so what matters is
+    // whether that directory takes new files. The probe is removed again when dropped.
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from("."));
+    Ok(match tempfile::NamedTempFile::new_in(&dir) {
+        Ok(_) if path.exists() => WritePermission::Writable,
+        Ok(_) => WritePermission::FileMissing,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => WritePermission::NeedsElevation,
+        Err(e) => WritePermission::OtherError(e.to_string()),
+    })
+}
+
+/// The crate version, shared by `--version`, `app_version` and the generated hosts header.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Which build is running, for the about box and bug reports.
+#[derive(Debug, Serialize, Clone)]
+pub struct AppVersion {
+    pub version: String,
+    /// Short commit hash the binary was built from, "unknown" outside a git checkout
+    pub git_sha: String,
+    /// RFC 3339 (UTC) time of the build, or "unknown"
+    pub build_date: String,
+    /// `<os>-<arch>`, e.g. `windows-x86_64`
+    pub os: String,
+}
+
+impl std::fmt::Display for AppVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hostly {} ({}, built {}, {})", self.version, self.git_sha, self.build_date, self.os)
+    }
+}
+
+/// Build details come from build.rs; missing ones (e.g. a build without it) read "unknown".
+#[tauri::command]
+pub fn app_version() -> AppVersion {
+    let build_date = option_env!("HOSTLY_BUILD_TIMESTAMP")
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    AppVersion {
+        version: VERSION.to_string(),
+        git_sha: option_env!("HOSTLY_GIT_SHA").unwrap_or("unknown").to_string(),
+        build_date,
+        os: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+    }
+}
+
+/// Whether Hostly runs with admin/root rights, and whether it can write the hosts file.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ElevationStatus {
+    pub is_elevated: bool,
+    pub can_write_hosts: bool,
+}
+
+/// Lets the GUI flag a missing elevation up front instead of on the first failed write.
+#[tauri::command]
+pub fn elevation_status() -> ElevationStatus {
+    let is_elevated = is_elevated();
+    let can_write_hosts = match check_write_permission() {
+        Ok(WritePermission::Writable) | Ok(WritePermission::FileMissing) => true,
+        // An elevated write clears a read-only attribute first, so the probe is too strict there
+        Ok(WritePermission::NeedsElevation) => is_elevated,
+        _ => false,
+    };
+    ElevationStatus { is_elevated, can_write_hosts }
+}
+
+/// The one place that decides whether we run as admin (Windows) or root (Unix).
+#[cfg(target_os = "windows")]
+pub fn is_elevated() -> bool {
+    use std::os::windows::process::CommandExt;
+    // `net session` needs admin rights; CREATE_NO_WINDOW keeps the GUI from flashing a console
+    std::process::Command::new("net")
+        .arg("session")
+        .creation_flags(0x08000000)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// The one place that decides whether we run as admin (Windows) or root (Unix).
+#[cfg(not(target_os = "windows"))]
+pub fn is_elevated() -> bool {
+    std::process::Command::new("id")
+        .arg("-u")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "0")
+        .unwrap_or(false)
+}
+
+/// Clears the OS resolver cache so freshly written entries take effect immediately.
+#[tauri::command]
+pub fn flush_dns() -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        run_flush_command("ipconfig", &["/flushdns"])
+    }
+    #[cfg(target_os = "macos")]
+    {
+        run_flush_command("dscacheutil", &["-flushcache"])?;
+        run_flush_command("killall", &["-HUP", "mDNSResponder"])
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // Only systemd-resolved keeps a cache we can flush. Without it (nscd-less
+        // distros, containers) lookups already read the hosts file fresh
+        if !Path::new("/run/systemd/resolve").exists() {
+            return Ok(());
+        }
+        run_flush_command("resolvectl", &["flush-caches"]).or_else(|first_err| {
+            run_flush_command("systemd-resolve", &["--flush-caches"])
+                .map_err(|second_err| format!("{}; {}", first_err, second_err))
+        })
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        Ok(())
+    }
+}
+
+fn run_flush_command(program: &str, args: &[&str]) -> Result<(), String> {
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args);
+
+    #[cfg(target_os = "windows")]
+    {
+        // CREATE_NO_WINDOW: don't flash a console from the GUI
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+
+    let output = cmd.output().map_err(|e| format!("{} spawn failed: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} failed (code {:?}): {}",
+            program,
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// What `auto_activate` matches profile rules against. Either part is None when it
+/// can't be determined on this platform or network (wired, offline, missing tools).
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct NetworkInfo {
+    pub ssid: Option<String>,
+    /// Lowercase, colon separated, zero padded: `aa:bb:cc:01:02:03`
+    pub gateway_mac: Option<String>,
+}
+
+#[tauri::command]
+pub async fn current_network() -> Result<NetworkInfo, String> {
+    // Asking netsh/nmcli/arp can take a while; keep it off the async workers
+    tauri::async_runtime::spawn_blocking(detect_network).await.map_err(|e| e.to_string())
+}
+
+/// Blocks while the platform's network tools run; the CLI calls it directly.
+pub fn detect_network() -> NetworkInfo {
+    NetworkInfo { ssid: current_ssid(), gateway_mac: gateway_ip().and_then(|ip| mac_for_ip(&ip)) }
+}
+
+/// Stdout of a successful run, or None if the tool is missing or fails.
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000);
+    }
+
+    let output = cmd.output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Value after the first `<key> ... : ` line, e.g. `    SSID   : Office` from netsh.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn field_after_colon(text: &str, key: &str) -> Option<String> {
+    text.lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn current_ssid() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        field_after_colon(&command_stdout("netsh", &["wlan", "show", "interfaces"])?, "SSID")
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // en0 is only the usual Wi-Fi device; machines with a built-in Ethernet port move it
+        let device = command_stdout("networksetup", &["-listallhardwareports"])
+            .and_then(|ports| wifi_device(&ports))
+            .unwrap_or_else(|| "en0".to_string());
+        let out = command_stdout("networksetup", &["-getairportnetwork", &device])?;
+        out.trim().strip_prefix("Current Wi-Fi Network: ").map(|s| s.to_string())
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // NetworkManager first, then wireless-tools
+        command_stdout("nmcli", &["-t", "-f", "active,ssid", "dev", "wifi"])
+            .and_then(|out| out.lines().find_map(|l| l.strip_prefix("yes:").map(nmcli_unescape)))
+            .or_else(|| command_stdout("iwgetid", &["-r"]).map(|s| s.trim().to_string()))
+            .filter(|s| !s.is_empty())
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+/// The device of the Wi-Fi (or, on older releases, AirPort) port in
+/// `networksetup -listallhardwareports` output.
+#[cfg(any(target_os = "macos", test))]
+fn wifi_device(ports: &str) -> Option<String> {
+    let mut lines = ports.lines().map(str::trim);
+    lines.find(|l| matches!(l.strip_prefix("Hardware Port: "), Some("Wi-Fi" | "AirPort")))?;
+    lines.next()?.strip_prefix("Device: ").map(|d| d.trim().to_string())
+}
+
+/// Undoes the `\:` and `\\` escaping that `nmcli -t` applies to field values.
+#[cfg(any(target_os = "linux", test))]
+fn nmcli_unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn gateway_ip() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        // "Default Gateway . . . . . . . . . : 192.168.1.1", skipping adapters without one
+        command_stdout("ipconfig", &[])?
+            .lines()
+            .filter(|l| l.trim_start().starts_with("Default Gateway"))
+            .filter_map(|l| l.split_once(':').map(|(_, v)| v.trim().to_string()))
+            .find(|v| v.parse::<std::net::Ipv4Addr>().is_ok())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        field_after_colon(&command_stdout("route", &["-n", "get", "default"])?, "gateway")
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // Destination 00000000 is the default route; the gateway is little-endian hex
+        let routes = fs::read_to_string("/proc/net/route").ok()?;
+        routes.lines().skip(1).find_map(|l| {
+            let cols: Vec<&str> = l.split_whitespace().collect();
+            if cols.get(1) != Some(&"00000000") {
+                return None;
+            }
+            let raw = u32::from_str_radix(cols.get(2)?, 16).ok()?;
+            Some(std::net::Ipv4Addr::from(raw.to_le_bytes()).to_string())
+        })
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+/// Looks the gateway up in the ARP table, which has it as soon as any traffic went out.
+fn mac_for_ip(ip: &str) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    let table = fs::read_to_string("/proc/net/arp").ok()?;
+    #[cfg(target_os = "windows")]
+    let table = command_stdout("arp", &["-a", ip])?;
+    #[cfg(target_os = "macos")]
+    let table = command_stdout("arp", &["-n", ip])?;
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    let table = String::new();
+
+    table
+        .lines()
+        .filter(|l| l.split_whitespace().any(|w| w.trim_matches(|c| c == '(' || c == ')') == ip))
+        .find_map(|l| l.split_whitespace().find_map(normalize_mac))
+}
+
+/// `AA-BB-CC-1-2-3` / `aa:bb:cc:01:02:03` -> `aa:bb:cc:01:02:03`; None if it isn't a MAC.
+/// The all-zero address of an incomplete ARP entry doesn't count.
+pub(crate) fn normalize_mac(text: &str) -> Option<String> {
+    let parts: Vec<&str> = text.split([':', '-']).collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    let bytes: Vec<u8> = parts
+        .iter()
+        .map(|p| if p.len() <= 2 { u8::from_str_radix(p, 16).ok() } else { None })
+        .collect::<Option<_>>()?;
+    if bytes.iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some(bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"))
+}
+
+#[tauri::command]
+pub fn hostly_open_url(url: String) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("cmd")
+            .args(&["/C", "start", &url])
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&url)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&url)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Environment variables are process-wide; tests that set them hold this guard.
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Points HOSTLY_HOSTS_PATH and HOSTLY_DATA_DIR into a scratch dir holding `hosts`.
+    pub(crate) fn scratch_system(hosts: &str) -> (MutexGuard<'static, ()>, tempfile::TempDir) {
+        let guard = lock_env();
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("hosts"), hosts).unwrap();
+        std::env::set_var("HOSTLY_HOSTS_PATH", dir.path().join("hosts"));
+        std::env::set_var(crate::storage::DATA_DIR_ENV, dir.path().join("data"));
+        (guard, dir)
+    }
+
+    #[test]
+    fn disable_all_keeps_lines_outside_the_managed_block() {
+        let (_guard, _dir) = scratch_system(
+            "127.0.0.1 localhost\n10.1.1.1 manual.local\n\n# HOSTLY:BEGIN\n10.0.0.1 api.local\n# HOSTLY:END\n",
+        );
+        disable_all(&Context::Headless).unwrap();
+        assert_eq!(get_system_hosts().unwrap(), "127.0.0.1 localhost\n10.1.1.1 manual.local\n");
+    }
+
+    #[test]
+    fn disable_all_writes_the_baseline_when_only_hostly_content_is_left() {
+        let (_guard, _dir) = scratch_system("# HOSTLY:BEGIN\n10.0.0.1 api.local\n# HOSTLY:END\n");
+        disable_all(&Context::Headless).unwrap();
+        assert_eq!(get_system_hosts().unwrap(), baseline_hosts());
+    }
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "HOST_IP" => Some("10.0.0.5".to_string()),
+            "DOMAIN" => Some("example.test".to_string()),
+            "NESTED" => Some("${HOST_IP}".to_string()),
+            "TWO_WORDS" => Some("10.0.0.5 evil.test".to_string()),
+            "NOT_AN_IP" => Some("localhost".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn substitute_variables_fills_in_defined_names() {
+        let content = "${HOST_IP} api.${DOMAIN} # ${HOST_IP} stays in comments\n127.0.0.1 localhost";
+        assert_eq!(
+            substitute_variables(content, vars).unwrap(),
+            "10.0.0.5 api.example.test # ${HOST_IP} stays in comments\n127.0.0.1 localhost"
+        );
+    }
+
+    #[test]
+    fn substitute_variables_reports_undefined_names_with_their_line() {
+        let errors = substitute_variables("127.0.0.1 localhost\n${MISSING} api.local", vars).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+        assert!(errors[0].reason.contains("undefined variable 'MISSING'"));
+    }
+
+    #[test]
+    fn substitute_variables_neither_nests_nor_expands_escapes() {
+        // A value is never expanded again, so it has to be a literal address here
+        let errors = substitute_variables("${NESTED} api.local", vars).unwrap_err();
+        assert!(errors[0].reason.contains("after substitution"));
+
+        assert_eq!(substitute_variables("${HOST_IP} a$${b}.local", vars).unwrap(), "10.0.0.5 a${b}.local");
+        assert!(substitute_variables("${HOST_IP api.local", vars).unwrap_err()[0].reason.contains("unclosed"));
+        assert!(substitute_variables("${1BAD} api.local", vars).unwrap_err()[0].reason.contains("invalid variable name"));
+    }
+
+    #[test]
+    fn substitute_variables_rejects_values_that_break_the_line() {
+        let errors = substitute_variables("${TWO_WORDS} api.local", vars).unwrap_err();
+        assert!(errors[0].reason.contains("single word"));
+        assert!(check_variable_value("X", "a\nb").is_err());
+        assert!(check_variable_value("X", "").is_err());
+        assert!(check_variable_value("X", "1.2.3.4#x").is_err());
+
+        let errors = substitute_variables("${NOT_AN_IP} api.local", vars).unwrap_err();
+        assert!(errors[0].reason.contains("invalid IP address 'localhost'"));
+    }
+
+    #[test]
+    fn system_hosts_round_trip_through_the_path_override() {
+        let (_guard, dir) = scratch_system("127.0.0.1 localhost\n");
+        assert_eq!(get_hosts_path(), dir.path().join("hosts"));
+        assert_eq!(get_system_hosts().unwrap(), "127.0.0.1 localhost\n");
+
+        save_system_hosts_internal(&Context::Headless, "127.0.0.1 localhost\n10.0.0.1 api.local\n".to_string()).unwrap();
+        assert_eq!(get_system_hosts().unwrap(), "127.0.0.1 localhost\n10.0.0.1 api.local\n");
+        // The previous content became the undo snapshot
+        undo_last_apply(&Context::Headless).unwrap();
+        assert_eq!(get_system_hosts().unwrap(), "127.0.0.1 localhost\n");
+    }
+
+    #[test]
+    fn elevated_write_quotes_custom_paths() {
+        assert_eq!(shell_quote("/tmp/my hosts"), "'/tmp/my hosts'");
+        assert_eq!(shell_quote("/tmp/it's"), "'/tmp/it'\\''s'");
+        assert_eq!(applescript_string(r#"mv '/a "b"\c'"#), r#""mv '/a \"b\"\\c'""#);
+    }
+
+    fn lint_rules(content: &str) -> Vec<(usize, &'static str)> {
+        lint_hosts(content).iter().map(|l| (l.line, l.rule.name())).collect()
+    }
+
+    #[test]
+    fn lint_flags_localhost_and_blackholed_public_names() {
+        assert_eq!(lint_rules("10.0.0.1 localhost"), vec![(1, "localhost_redefined")]);
+        assert_eq!(lint_rules("# system\n127.0.0.1 localhost\n::1 localhost"), vec![(2, "loopback_duplicate"), (3, "loopback_duplicate")]);
+        assert_eq!(lint_rules("0.0.0.0 ads.example.com\n127.0.0.1 api.test app"), vec![(1, "blackhole")]);
+        // Disabled entries aren't checked
+        assert!(lint_rules("# 0.0.0.0 ads.example.com").is_empty());
+    }
+
+    #[test]
+    fn lint_checks_wildcard_expansions_and_honours_lint_allow() {
+        assert_eq!(lint_rules("@wildcard 0.0.0.0 *.example.com ads tracker"), vec![(1, "blackhole")]);
+        assert!(lint_rules("@wildcard 127.0.0.1 *.dev.local api web").is_empty());
+
+        let allowed = "# @lint-allow blackhole, loopback_duplicate\n0.0.0.0 ads.example.com\n127.0.0.1 localhost\n10.0.0.1 localhost";
+        assert_eq!(lint_rules(allowed), vec![(4, "localhost_redefined")]);
+    }
+
+    #[test]
+    fn normalize_mac_accepts_common_notations() {
+        assert_eq!(normalize_mac("AA-BB-CC-1-2-3").as_deref(), Some("aa:bb:cc:01:02:03"));
+        assert_eq!(normalize_mac("aa:bb:cc:01:02:03").as_deref(), Some("aa:bb:cc:01:02:03"));
+        assert_eq!(normalize_mac("00:00:00:00:00:00"), None);
+        assert_eq!(normalize_mac("aa:bb:cc:01:02"), None);
+        assert_eq!(normalize_mac("aa:bb:cc:01:02:123"), None);
+        assert_eq!(normalize_mac("192.168.1.1"), None);
+    }
+
+    #[test]
+    fn network_tool_output_is_unpacked() {
+        assert_eq!(nmcli_unescape(r"Cafe\: Guest"), "Cafe: Guest");
+        assert_eq!(nmcli_unescape(r"back\\slash"), r"back\slash");
+
+        let ports = "Hardware Port: Ethernet\nDevice: en0\nEthernet Address: aa:bb:cc:dd:ee:ff\n\n\
+                     Hardware Port: Wi-Fi\nDevice: en1\nEthernet Address: aa:bb:cc:dd:ee:00\n";
+        assert_eq!(wifi_device(ports).as_deref(), Some("en1"));
+        assert_eq!(wifi_device("Hardware Port: Ethernet\nDevice: en0\n"), None);
+    }
+
+    #[test]
+    fn unified_diff_shows_changes_with_context() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new"), "");
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let diff = unified_diff(old, new, "old", "new");
+        assert!(diff.starts_with("--- old\n+++ new\n"), "{}", diff);
+        assert!(diff.contains(" 4\n-5\n+five\n 6\n"), "{}", diff);
+        assert!(diff.contains(" 8\n+9\n"), "{}", diff);
+        assert!(!diff.contains(" 1\n"), "{}", diff);
+    }
+
+    #[test]
+    fn oversized_diffs_fall_back_to_replacing_the_changed_block() {
+        let old: Vec<String> = (0..3000).map(|i| format!("10.0.0.1 old{}.local", i)).collect();
+        let new: Vec<String> = (0..3000).map(|i| format!("10.0.0.1 new{}.local", i)).collect();
+        let old = format!("# top\n{}\n# bottom\n", old.join("\n"));
+        let new = format!("# top\n{}\n# bottom\n", new.join("\n"));
+
+        let diff = unified_diff(&old, &new, "old", "new");
+        assert_eq!(diff.lines().filter(|l| l.starts_with("-1")).count(), 3000);
+        assert_eq!(diff.lines().filter(|l| l.starts_with("+1")).count(), 3000);
+        assert!(diff.contains(" # top\n-10.0.0.1 old0.local\n"), "{}", &diff[..200]);
+        assert!(diff.contains("+10.0.0.1 new2999.local\n # bottom\n"));
+    }
+
+    #[test]
+    fn write_permission_probes_the_hosts_directory() {
+        let (_guard, dir) = scratch_system("127.0.0.1 localhost\n");
+        assert_eq!(check_write_permission().unwrap(), WritePermission::Writable);
+
+        fs::remove_file(get_hosts_path()).unwrap();
+        assert_eq!(check_write_permission().unwrap(), WritePermission::FileMissing);
+        // The probe leaves nothing behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn readonly_hosts_are_written_and_marked_readonly_again() {
+        let (_guard, _dir) = scratch_system("127.0.0.1 localhost\n");
+        let path = get_hosts_path();
+        let set_readonly = |value: bool| {
+            let mut perms = fs::metadata(&path).unwrap().permissions();
+            perms.set_readonly(value);
+            fs::set_permissions(&path, perms).unwrap();
+        };
+        let readonly = || fs::metadata(&path).unwrap().permissions().readonly();
+        set_readonly(true);
+
+        write_system_hosts("10.0.0.1 api.local\n".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "10.0.0.1 api.local\n");
+        assert!(readonly());
+
+        // Also restored when the write itself fails
+        let failed = with_readonly_cleared(&path, || -> std::io::Result<()> { Err(std::io::ErrorKind::Other.into()) });
+        assert!(failed.is_err());
+        assert!(readonly());
+
+        // Or the temp dir can't clean it up
+        set_readonly(false);
+    }
+
+    #[test]
+    fn failed_writes_leave_the_original_hosts_alone() {
+        use std::io::Write;
+
+        let (_guard, dir) = scratch_system("127.0.0.1 localhost\n10.0.0.1 keep.local\n");
+        let path = get_hosts_path();
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"10.0.0.2 half")?;
+            Err(std::io::Error::other("disk full"))
+        });
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Other);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "127.0.0.1 localhost\n10.0.0.1 keep.local\n");
+        // The temp file went with the error
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn saved_hosts_keep_the_line_ending_of_the_original() {
+        let new = "127.0.0.1 localhost\n10.0.0.1 api.local\n";
+        for (original, expected) in [
+            ("127.0.0.1 localhost\r\n::1 localhost\r\n", "127.0.0.1 localhost\r\n10.0.0.1 api.local\r\n"),
+            ("127.0.0.1 localhost\n::1 localhost\n", new),
+            // Mixed files go with the majority
+            ("127.0.0.1 localhost\r\n::1 localhost\r\n# note\n", "127.0.0.1 localhost\r\n10.0.0.1 api.local\r\n"),
+            ("127.0.0.1 localhost\r\n::1 localhost\n# note\n", new),
+        ] {
+            let (_guard, _dir) = scratch_system(original);
+            save_system_hosts_internal(&Context::Headless, new.to_string()).unwrap();
+            assert_eq!(get_system_hosts().unwrap(), expected, "original {:?}", original);
+        }
+    }
+
+    #[test]
+    fn line_ending_detection_falls_back_to_the_platform() {
+        assert_eq!(detect_line_ending("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(detect_line_ending("a\nb\r\nc\n"), LineEnding::Lf);
+        assert_eq!(detect_line_ending(""), LineEnding::native());
+        assert_eq!(detect_line_ending("a\r\nb\n"), LineEnding::native());
+    }
+
+    #[test]
+    fn normalize_canonicalizes_ipv6_and_lowercases_hostnames() {
+        let content = "0:0:0:0:0:0:0:1\tLocalHost   IP6-Loopback\n\
+                       FE80:0000::0001%eth0 Router.LAN\n\
+                       #  2001:DB8:0:0:0:0:0:1   Docs.Example.COM\n\
+                       # Keep   THIS comment\n\
+                       10.0.0.1  API.local   # Trailing   Note\n";
+        assert_eq!(
+            normalize_profile(content),
+            "::1 localhost ip6-loopback\n\
+             fe80::1%eth0 router.lan\n\
+             # 2001:db8::1 docs.example.com\n\
+             # Keep   THIS comment\n\
+             10.0.0.1 api.local # Trailing   Note\n"
+        );
+    }
+
+    #[test]
+    fn validate_content_reports_each_bad_line() {
+        let content = "# comment\n\
+                       ::1 localhost\n\
+                       fe80::1%lo0 router.local\n\
+                       2001:db8::zz bad6.local\n\
+                       10.0.0.1\n\
+                       10.0.0.256 bad4.local # typo\n\
+                       10.0.0.2 ok.local\n";
+        let errors = validate_content(content.to_string()).unwrap();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.reason.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (4, "invalid IP address '2001:db8::zz'"),
+                (5, "missing hostname"),
+                (6, "invalid IP address '10.0.0.256'"),
+            ]
+        );
+        assert_eq!(errors[2].text, "10.0.0.256 bad4.local # typo");
+        assert!(validate_content("::1 localhost\n127.0.0.1 localhost".to_string()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn wildcard_directives_expand_in_place() {
+        let content = "# dev\n@wildcard 127.0.0.1 *.myapp.local a b  # front\n10.0.0.1 db.local";
+        assert_eq!(
+            expand_directives(content).unwrap(),
+            "# dev\n# @wildcard 127.0.0.1 *.myapp.local a b  # front\n127.0.0.1 a.myapp.local\n127.0.0.1 b.myapp.local\n10.0.0.1 db.local"
+        );
+        // Commented-out directives are left alone
+        assert_eq!(expand_directives("# @wildcard 1.2.3.4 *.x a").unwrap(), "# @wildcard 1.2.3.4 *.x a");
+    }
+
+    #[test]
+    fn malformed_directives_error_with_their_line() {
+        let content = "10.0.0.1 ok.local\n\
+                       @wildcard 127.0.0.1 myapp.local a\n\
+                       @wildcard 999.0.0.1 *.myapp.local a\n\
+                       @wildcard 127.0.0.1 *.myapp.local\n\
+                       @wildcard 127.0.0.1 *.myapp.local a.\n\
+                       @include other.txt\n\
+                       @wildcard";
+        let errors = expand_directives(content).unwrap_err();
+        let found: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.reason.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, "@wildcard pattern must look like '*.<domain>'"),
+                (3, "invalid IP address '999.0.0.1'"),
+                (4, "@wildcard lists no names to expand"),
+                (5, "invalid name 'a.' in @wildcard"),
+                (6, "unknown directive '@include'"),
+                (7, "@wildcard needs an IP, a '*.<domain>' pattern and names"),
+            ]
+        );
+        // The validator reports the same lines, so the editor flags them before any apply
+        assert_eq!(validate_hosts(content).unwrap_err().len(), 6);
+    }
+
+    #[test]
+    fn entry_comments_survive_a_round_trip() {
+        let content = "# Staging servers\n\
+                       10.0.0.1   api.local   # staging API\n\
+                       # 10.0.0.2 old.local # retired\n\
+                       10.0.0.3 db.local\n\
+                       \n\
+                       10.0.0.4 cache.local #tight";
+        let entries = parse_profile(content);
+        assert_eq!(serialize_profile(&entries), content);
+
+        let comments: Vec<Option<&str>> = entries.iter().map(|e| e.comment.as_deref()).collect();
+        assert_eq!(
+            comments,
+            vec![Some("Staging servers"), Some("staging API"), Some("retired"), None, None, Some("tight")]
+        );
+
+        // Edited entries are rebuilt, keeping the label on the same line
+        let mut edited = entries.clone();
+        edited[1].ip = "10.0.0.9".to_string();
+        edited[2].enabled = true;
+        edited[3].comment = Some("primary".to_string());
+        assert_eq!(
+            serialize_profile(&edited),
+            "# Staging servers\n\
+             10.0.0.9 api.local # staging API\n\
+             10.0.0.2 old.local # retired\n\
+             10.0.0.3 db.local # primary\n\
+             \n\
+             10.0.0.4 cache.local #tight"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-mod hosts;
+pub mod hosts;
 pub mod storage;
 pub mod cli;
 
@@ -14,7 +14,7 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .setup(|app| {
             // Check CLI args
-            if cli::run_cli(Some(&app.handle())) {
+            if cli::run_cli(Some(app.handle())) {
                 std::process::exit(0);
             }
 
@@ -26,11 +26,19 @@ pub fn run() {
             }
             
             // Apply Window Settings
-            let ctx = storage::Context::Tauri(&app.handle());
+            let ctx = storage::Context::Tauri(app.handle());
             if let Ok(config) = storage::load_config_internal(&ctx) {
                 if let (Some(w), Some(h)) = (config.window_width, config.window_height) {
                      let _ = window.set_size(tauri::Size::Logical(tauri::LogicalSize { width: w, height: h }));
                 }
+
+                // main.rs has already relaunched as admin where needed; a failure here
+                // shouldn't keep the window from opening
+                if config.reapply_on_start {
+                    if let Err(e) = storage::apply_config_internal(&ctx) {
+                        eprintln!("Failed to re-apply hosts on start: {}", e);
+                    }
+                }
             }
             
             // Show window after setup to prevent flashing/resizing jitter
@@ -53,26 +61,85 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             hosts::get_system_hosts,
+            hosts::get_system_hosts_parsed,
+            hosts::validate_content,
             hosts::save_system_hosts,
             hosts::check_write_permission,
+            hosts::elevation_status,
+            hosts::app_version,
+            hosts::current_network,
+            hosts::get_hosts_path_command,
+            hosts::flush_dns,
+            hosts::diff_config,
+            hosts::undo_apply,
+            hosts::disable_all_command,
             hosts::hostly_open_url,
             storage::load_config,
+            storage::get_paths,
+            storage::summary_line,
             storage::load_common_config,
             storage::save_common_config,
+            storage::list_common_snippets,
+            storage::save_common_snippet,
+            storage::delete_common_snippet,
             storage::list_profiles,
+            storage::list_active_profiles,
             storage::create_profile,
             storage::save_profile_content,
+            storage::save_profile_content_checked,
+            storage::toggle_entry,
+            storage::set_entry_ip,
+            storage::set_entry_comment,
+            storage::move_entry,
+            storage::set_all_entries_enabled,
             storage::delete_profile,
             storage::rename_profile,
+            storage::canonicalize_profile,
+            storage::set_profile_description,
+            storage::set_activation_rule,
+            storage::auto_activate,
+            storage::set_profile_group,
+            storage::duplicate_profile,
+            storage::set_profile_locked,
+            storage::search_profiles,
+            storage::check_reachability,
+            storage::diff_profiles,
+            storage::move_profile,
             storage::toggle_profile_active,
+            storage::set_active_profiles,
             storage::set_multi_select,
             storage::apply_config,
+            storage::preview_apply,
+            storage::export_clean_hosts,
+            storage::export_profile_snippet,
+            storage::import_profile_snippet,
+            storage::export_bundle,
+            storage::import_bundle,
+            storage::resolve_in_pending,
+            storage::entry_provenance,
+            storage::apply_config_with_summary,
+            storage::detect_conflicts,
+            storage::lint_pending,
+            storage::list_variables,
+            storage::set_variable,
+            storage::set_merge_mode,
+            storage::set_common_position,
+            storage::set_sort_entries,
+            storage::set_normalize,
+            storage::set_logging,
+            storage::set_reapply_on_start,
+            storage::set_apply_hook,
             storage::import_file,
             storage::export_file,
             storage::import_data,
             storage::export_data,
             storage::import_switchhosts,
+            storage::export_switchhosts,
             storage::update_remote_config,
+            storage::import_from_url,
+            storage::import_plain_hosts,
+            storage::import_current_hosts,
+            storage::snapshot_system_hosts,
             storage::trigger_profile_update,
             storage::set_theme,
             storage::save_window_config,
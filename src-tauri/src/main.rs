@@ -8,17 +8,8 @@
 fn check_admin_and_relaunch() {
     #[cfg(all(target_os = "windows", feature = "auto-elevation"))]
     {
-         let output = std::process::Command::new("net")
-            .arg("session")
-            .output();
-            
-         let is_admin = match output {
-             Ok(o) => o.status.success(),
-             Err(_) => false,
-         };
-
-         if !is_admin {
-             println!("Not running as admin, attempting to relaunch with RunAs...");
+         if !hostly_lib::hosts::is_elevated() {
+             eprintln!("Not running as admin, attempting to relaunch with RunAs...");
              let current_exe = std::env::current_exe().unwrap();
              let args: Vec<String> = std::env::args().skip(1).collect();
              let args_str = args.iter().map(|arg| {
@@ -44,9 +35,20 @@ fn check_admin_and_relaunch() {
     }
 }
 
+/// `HOSTLY_NO_ELEVATE` or `--no-elevate` keep Hostly unprivileged, e.g. to browse
+/// profiles or in automation. Writes then fail with a permission error instead.
+fn elevation_disabled() -> bool {
+    let env_set = std::env::var("HOSTLY_NO_ELEVATE")
+        .map(|v| !v.trim().is_empty() && v.trim() != "0")
+        .unwrap_or(false);
+    env_set || std::env::args().skip(1).any(|a| a == "--no-elevate")
+}
+
 fn main() {
-    println!("Starting Hostly...");
-    check_admin_and_relaunch();
+    // Read-only commands shouldn't cost a UAC prompt
+    if !elevation_disabled() && hostly_lib::cli::needs_elevation() {
+        check_admin_and_relaunch();
+    }
 
     hostly_lib::run()
 }
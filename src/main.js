@@ -2,6 +2,7 @@ const tauri = window.__TAURI__ || {};
 const { invoke } = tauri.core || {};
 const { ask, message, open, save: saveDialog } = tauri.dialog || {};
 const { readTextFile, writeTextFile } = tauri.fs || {}; // We'll use backend commands instead
+const { listen } = tauri.event || {};
 
 console.log('Tauri APIs initialized:', {
     hasInvoke: !!invoke,
@@ -181,6 +182,11 @@ function showToast(text, type = 'info', duration = 3000) {
     }, duration);
 }
 
+// Profile commands reject with { code, message }; everything else with a plain string
+function errorText(e) {
+    return e && typeof e === 'object' && 'message' in e ? e.message : e;
+}
+
 // Functions
 async function loadData() {
     console.log('loadData starting...');
@@ -214,7 +220,7 @@ async function loadData() {
         }
     } catch (e) {
         console.error('loadData error:', e);
-        showToast(`加载失败: ${e}`, 'error');
+        showToast(`加载失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -273,7 +279,7 @@ async function updateRemoteProfile(id, name) {
             showToast('更新成功', 'success');
         } catch (e) {
             console.error(e);
-            showToast(`更新失败: ${e}`, 'error');
+            showToast(`更新失败: ${errorText(e)}`, 'error');
         }
     }
 }
@@ -363,7 +369,7 @@ async function manualRefreshRemote(id) {
         }
         showToast('刷新成功', 'success');
     } catch (e) {
-        showToast(`刷新失败: ${e}`, 'error');
+        showToast(`刷新失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -496,7 +502,7 @@ async function saveCurrent() {
         }
         showToast('保存成功', 'success');
     } catch (e) {
-        showToast(`保存失败: ${e}`, 'error');
+        showToast(`保存失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -529,7 +535,7 @@ async function toggleProfile(id) {
             editor.value = systemContent;
         }
     } catch (e) {
-        showToast(`切换失败: ${e}`, 'error');
+        showToast(`切换失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -564,7 +570,7 @@ async function createProfile(data) {
                  showToast('远程配置下载成功', 'success');
              } catch (e) {
                  console.error('Download failed:', e);
-                 showToast(`下载失败: ${e}`, 'error');
+                 showToast(`下载失败: ${errorText(e)}`, 'error');
              }
         }
 
@@ -574,7 +580,7 @@ async function createProfile(data) {
         showToast('创建成功 (部分加载中)', 'success');
     } catch (e) {
         console.error('Create profile error:', e);
-        showToast(`创建失败: ${e}`, 'error');
+        showToast(`创建失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -594,7 +600,7 @@ async function deleteProfile(id, name) {
             await loadData();
             showToast('已删除', 'info');
         } catch (e) {
-            showToast(`删除失败: ${e}`, 'error');
+            showToast(`删除失败: ${errorText(e)}`, 'error');
         }
     }
 }
@@ -657,7 +663,7 @@ async function editProfile() {
             
         } catch (e) {
             console.error(e);
-            showToast(`修改失败: ${e}`, 'error');
+            showToast(`修改失败: ${errorText(e)}`, 'error');
         }
     });
 }
@@ -682,7 +688,8 @@ async function importData() {
         try {
             const content = await invoke('import_file', { path: selected });
             if (selected.endsWith('.json')) {
-                await invoke('import_data', { jsonContent: content });
+                const skipped = await invoke('import_data', { jsonContent: content });
+                skipped.forEach((msg) => showToast(msg, 'info', 6000));
             } else {
                 const name = selected.split(/[\/\\]/).pop().split('.')[0];
                 await invoke('create_profile', { name, content });
@@ -690,7 +697,7 @@ async function importData() {
             await loadData();
             showToast('导入成功', 'success');
         } catch (e) {
-            showToast(`导入失败: ${e}`, 'error');
+            showToast(`导入失败: ${errorText(e)}`, 'error');
         }
     }
 }
@@ -707,7 +714,7 @@ async function importSwitchHosts() {
             showToast(`已从 SwitchHosts 导入 ${count} 个环境`, 'success');
         }
     } catch (e) {
-        showToast(`导入失败: ${e}`, 'error');
+        showToast(`导入失败: ${errorText(e)}`, 'error');
     }
 }
 
@@ -723,7 +730,7 @@ async function exportAll() {
             await invoke('export_file', { path, content: data });
             showToast('导出成功', 'success');
         } catch (e) {
-            showToast(`导出失败: ${e}`, 'error');
+            showToast(`导出失败: ${errorText(e)}`, 'error');
         }
     }
 }
@@ -750,7 +757,7 @@ async function exportCurrentProfile() {
              await invoke('export_file', { path, content });
              showToast('导出成功', 'success');
         } catch (e) {
-             showToast(`导出失败: ${e}`, 'error');
+             showToast(`导出失败: ${errorText(e)}`, 'error');
         }
     }
 }
@@ -940,6 +947,12 @@ window.addEventListener('DOMContentLoaded', async () => {
     await initSidebarWidth();
     await loadData();
     selectProfile('system');
+    // The hosts file was written, but something after it (e.g. the apply hook) failed
+    if (listen) {
+        listen('apply-errors', (event) => {
+            event.payload.forEach((msg) => showToast(msg, 'error', 6000));
+        });
+    }
     // Show window only after everything is ready to avoid flash
     setTimeout(() => {
         invoke('show_main_window');